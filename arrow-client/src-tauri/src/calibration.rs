@@ -0,0 +1,174 @@
+use crate::events::{CalibrationProgress, Point3D};
+use crate::fusion::Measured;
+use serde::{Deserialize, Serialize};
+use tokio::time::{Duration, Instant};
+
+pub const DEFAULT_CALIBRATION: Duration = Duration::from_secs(5);
+// Shorter calibrations don't average out the noise of the landmarks
+pub const MIN_CALIBRATION: Duration = Duration::from_secs(2);
+pub const MAX_CALIBRATION: Duration = Duration::from_secs(30);
+
+// Fewer frames with the ears and shoulders visible fail the calibration
+const MIN_SAMPLES: usize = 10;
+// A measure spread wider than this means the user moved during the calibration
+const MAX_DEPTH_SPREAD: f32 = 0.15;
+const MAX_SLOPE_SPREAD: f32 = 0.08;
+
+/*
+What the posture is classified from, computed from the ears and the shoulders.
+Depths are the MediaPipe z coordinates, smaller is closer to the camera,
+and slopes are the height difference between the left and right landmarks over their distance.
+A side camera has no depth to compare, it measures how far forward the head is in image widths.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PostureMeasures {
+    // How much closer to the camera the ears are than the shoulders
+    pub depth_gap: f32,
+    pub shoulder_depth: f32,
+    pub ear_slope: f32,
+    pub shoulder_slope: f32,
+    // How far ahead of the shoulder a side camera sees the ear
+    pub side_gap: f32,
+}
+
+impl PostureMeasures {
+    pub fn from_points(
+        left_ear: &Point3D,
+        right_ear: &Point3D,
+        left_shoulder: &Point3D,
+        right_shoulder: &Point3D,
+    ) -> Self {
+        let avg_ear_depth = (left_ear.z + right_ear.z) / 2.0;
+        let avg_shoulder_depth = (left_shoulder.z + right_shoulder.z) / 2.0;
+
+        Self {
+            depth_gap: avg_shoulder_depth - avg_ear_depth,
+            shoulder_depth: avg_shoulder_depth,
+            ear_slope: (left_ear.y - right_ear.y) / (left_ear.x - right_ear.x),
+            shoulder_slope: (left_shoulder.y - right_shoulder.y)
+                / (left_shoulder.x - right_shoulder.x),
+            // Only measured from a side
+            side_gap: 0.0,
+        }
+    }
+
+    // Deviation from the neutral posture of the user
+    pub fn relative_to(&self, baseline: &PostureBaseline) -> Self {
+        Self {
+            depth_gap: self.depth_gap - baseline.depth_gap,
+            shoulder_depth: self.shoulder_depth - baseline.shoulder_depth,
+            ear_slope: self.ear_slope - baseline.ear_slope,
+            shoulder_slope: self.shoulder_slope - baseline.shoulder_slope,
+            side_gap: self.side_gap - baseline.side_gap,
+        }
+    }
+
+    fn is_finite(&self) -> bool {
+        [
+            self.depth_gap,
+            self.shoulder_depth,
+            self.ear_slope,
+            self.shoulder_slope,
+            self.side_gap,
+        ]
+        .iter()
+        .all(|measure| measure.is_finite())
+    }
+}
+
+/*
+Measures of the user sitting straight, saved in the settings.
+The thresholds apply to the deviations from it, so the camera placement and the build
+of the user don't count as a bad posture. The default of zeros is the uncalibrated posture.
+*/
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PostureBaseline {
+    pub depth_gap: f32,
+    pub shoulder_depth: f32,
+    pub ear_slope: f32,
+    pub shoulder_slope: f32,
+    pub side_gap: f32,
+    // Frames the baseline was measured from
+    pub samples: u32,
+    // Unix timestamp in milliseconds
+    pub calibrated_at: u64,
+}
+
+// Collects the measures of the frames classified while the user sits straight
+pub struct Calibration {
+    started: Instant,
+    duration: Duration,
+    samples: Vec<PostureMeasures>,
+}
+
+impl Calibration {
+    pub fn new(duration: Duration, now: Instant) -> Self {
+        Self {
+            started: now,
+            duration,
+            samples: Vec::new(),
+        }
+    }
+
+    // Frames without the ears and shoulders clearly visible are skipped
+    pub fn add_measures(&mut self, measured: &Measured) {
+        if let Ok(measures) = measured {
+            if measures.is_finite() {
+                self.samples.push(*measures);
+            }
+        }
+    }
+
+    pub fn is_done(&self, now: Instant) -> bool {
+        now.duration_since(self.started) >= self.duration
+    }
+
+    pub fn get_progress(&self, now: Instant) -> CalibrationProgress {
+        let elapsed = now.duration_since(self.started).min(self.duration);
+        CalibrationProgress {
+            progress: elapsed.as_secs_f32() / self.duration.as_secs_f32(),
+            samples: self.samples.len() as u32,
+            elapsed_ms: elapsed.as_millis() as u64,
+            duration_ms: self.duration.as_millis() as u64,
+        }
+    }
+
+    // The median of every measure, robust to a few frames of bad landmarks
+    pub fn finish(self, calibrated_at: u64) -> Result<PostureBaseline, String> {
+        if self.samples.len() < MIN_SAMPLES {
+            return Err(format!(
+                "Only {} frames with the ears and shoulders visible, check the camera and try again",
+                self.samples.len()
+            ));
+        }
+
+        let measure = |get: fn(&PostureMeasures) -> f32| {
+            let mut values: Vec<f32> = self.samples.iter().map(get).collect();
+            values.sort_by(f32::total_cmp);
+            let spread = values[values.len() * 9 / 10] - values[values.len() / 10];
+            (values[values.len() / 2], spread)
+        };
+        let (depth_gap, depth_spread) = measure(|measures| measures.depth_gap);
+        let (shoulder_depth, shoulder_depth_spread) = measure(|measures| measures.shoulder_depth);
+        let (ear_slope, ear_slope_spread) = measure(|measures| measures.ear_slope);
+        let (shoulder_slope, shoulder_slope_spread) = measure(|measures| measures.shoulder_slope);
+        let (side_gap, side_gap_spread) = measure(|measures| measures.side_gap);
+
+        if depth_spread.max(shoulder_depth_spread).max(side_gap_spread) > MAX_DEPTH_SPREAD
+            || ear_slope_spread.max(shoulder_slope_spread) > MAX_SLOPE_SPREAD
+        {
+            return Err("Too much movement, sit straight and still and try again".to_string());
+        }
+
+        Ok(PostureBaseline {
+            depth_gap,
+            shoulder_depth,
+            ear_slope,
+            shoulder_slope,
+            side_gap,
+            samples: self.samples.len() as u32,
+            calibrated_at,
+        })
+    }
+}
@@ -0,0 +1,240 @@
+use crate::db_manager::DbManager;
+use crate::stage_queue::StageQueue;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, LineWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncBufReadExt, BufReader, Lines};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep_until, Duration, Instant};
+
+// Lines received while the disk is busy, more are dropped
+const RECORDING_QUEUE_SIZE: usize = 256;
+
+/*
+Capture files are JSON lines.
+The first line is a header: {"type":"capture","version":1,"started_at":<unix ms>}
+Every other line is a line received from a posture source, handshake included:
+{"t_us":<microseconds since the start of the recording>,"source":"primary","line":"..."}
+*/
+pub const CAPTURE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CaptureHeader {
+    #[serde(rename = "type")]
+    file_type: String,
+    version: u32,
+    started_at: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CaptureEntry {
+    pub t_us: u64,
+    pub source: String,
+    pub line: String,
+}
+
+// Writes every line received from the sources to a capture file while recording
+pub struct CaptureWriter {
+    path: PathBuf,
+    file: LineWriter<File>,
+    started: Instant,
+}
+
+impl CaptureWriter {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut file = LineWriter::new(File::create(path)?);
+        let header = CaptureHeader {
+            file_type: "capture".to_string(),
+            version: CAPTURE_VERSION,
+            started_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_millis() as u64)
+                .unwrap_or(0),
+        };
+        writeln!(file, "{}", serde_json::to_string(&header)?)?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            started: Instant::now(),
+        })
+    }
+
+    // New capture file named after the current time in the app data directory
+    pub fn create_default() -> io::Result<Self> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        let path = DbManager::get_app_data_dir()
+            .join("captures")
+            .join(format!("capture-{}.jsonl", timestamp));
+
+        Self::create(&path)
+    }
+
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    // `received_at` is when the source received the line, not when it gets written
+    pub fn write_line(&mut self, source: &str, line: &str, received_at: Instant) -> io::Result<()> {
+        let entry = CaptureEntry {
+            t_us: received_at
+                .saturating_duration_since(self.started)
+                .as_micros() as u64,
+            source: source.to_string(),
+            line: line.to_string(),
+        };
+        writeln!(self.file, "{}", serde_json::to_string(&entry)?)
+    }
+}
+
+struct RecordedLine {
+    source: String,
+    line: String,
+    received_at: Instant,
+}
+
+/*
+A recording in progress. The lines are written by a blocking task fed through a stage queue,
+so the sources never wait on the disk: when it falls behind, lines are dropped from the capture.
+*/
+pub struct CaptureRecording {
+    path: PathBuf,
+    lines: StageQueue<RecordedLine>,
+    task: JoinHandle<()>,
+}
+
+impl CaptureRecording {
+    pub fn start(writer: CaptureWriter) -> Self {
+        let path = writer.get_path().to_path_buf();
+        let (lines, receiver) = StageQueue::new("recording", RECORDING_QUEUE_SIZE);
+        let task = tokio::task::spawn_blocking(move || write_lines(writer, receiver));
+
+        Self { path, lines, task }
+    }
+
+    pub fn record(&self, source: &str, line: &str) {
+        self.lines.push(RecordedLine {
+            source: source.to_string(),
+            line: line.to_string(),
+            received_at: Instant::now(),
+        });
+    }
+
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    // False once writing failed
+    pub fn is_active(&self) -> bool {
+        !self.task.is_finished()
+    }
+
+    // Waits for the queued lines to be written, returns the path of the capture
+    pub async fn finish(self) -> PathBuf {
+        let Self { path, lines, task } = self;
+        drop(lines);
+        let _ = task.await;
+        path
+    }
+}
+
+fn write_lines(mut writer: CaptureWriter, mut lines: mpsc::Receiver<RecordedLine>) {
+    while let Some(recorded) = lines.blocking_recv() {
+        // A failing disk stops the recording, never the connection
+        if let Err(e) = writer.write_line(&recorded.source, &recorded.line, recorded.received_at) {
+            eprintln!(
+                "Failed to write to {}, recording stopped: {}",
+                writer.get_path().display(),
+                e
+            );
+            return;
+        }
+    }
+}
+
+/*
+Plays a capture file back with the original timing, divided by `speed`.
+Only the lines recorded from `source` are replayed, or all of them if it is None.
+*/
+pub struct CaptureReplay {
+    lines: Lines<BufReader<tokio::fs::File>>,
+    source: Option<String>,
+    speed: f32,
+    started: Instant,
+    // Capture time of the first replayed line, so the replay starts right away
+    origin: Option<u64>,
+    // Read but not yet due, kept here so read_line stays cancel safe
+    pending: Option<CaptureEntry>,
+}
+
+impl CaptureReplay {
+    pub async fn open(path: &Path, source: Option<String>, speed: f32) -> io::Result<Self> {
+        let file = tokio::fs::File::open(path).await?;
+
+        Ok(Self {
+            lines: BufReader::new(file).lines(),
+            source,
+            speed,
+            started: Instant::now(),
+            origin: None,
+            pending: None,
+        })
+    }
+
+    // Returns the next recorded line once it is due, or None at the end of the capture
+    pub async fn read_line(&mut self) -> io::Result<Option<String>> {
+        if self.pending.is_none() {
+            self.pending = self.next_entry().await?;
+        }
+        let Some(entry) = &self.pending else {
+            return Ok(None);
+        };
+
+        let origin = *self.origin.get_or_insert(entry.t_us);
+        let offset = Duration::from_micros(entry.t_us.saturating_sub(origin));
+        // A corrupted timestamp or speed ends the replay instead of panicking
+        let due = Duration::try_from_secs_f64(offset.as_secs_f64() / self.speed as f64)
+            .ok()
+            .and_then(|delay| self.started.checked_add(delay))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "Capture line {}us after the first one can't be replayed",
+                        offset.as_micros()
+                    ),
+                )
+            })?;
+        sleep_until(due).await;
+
+        Ok(self.pending.take().map(|entry| entry.line))
+    }
+
+    async fn next_entry(&mut self) -> io::Result<Option<CaptureEntry>> {
+        while let Some(line) = self.lines.next_line().await? {
+            // Skips the header and lines that aren't entries
+            let Ok(entry) = serde_json::from_str::<CaptureEntry>(&line) else {
+                continue;
+            };
+            if self
+                .source
+                .as_ref()
+                .is_none_or(|source| *source == entry.source)
+            {
+                return Ok(Some(entry));
+            }
+        }
+
+        Ok(None)
+    }
+}
@@ -0,0 +1,330 @@
+use crate::calibration::{PostureBaseline, PostureMeasures};
+use crate::events::{PostureIssue, PostureMetrics, PostureUpdate};
+use crate::fusion::{measure_frame, Measured};
+use crate::landmarks::PoseLandmarks;
+use crate::postures::Posture;
+use crate::settings::{CameraView, ClassifierSettings, PostureThresholds};
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+/*
+Turns the measures of a frame into its posture issues.
+Frames the ears and shoulders can't be measured in never reach the classifier,
+`get_issues` reports them as not visible. Implementations must not block,
+they run for every frame on the classification stage of the pipeline.
+Leaving the `current` posture takes a measure `hysteresis` below what led to it,
+see `SmoothingSettings::hysteresis`.
+*/
+pub trait PostureClassifier: Send + Sync {
+    // Every issue, the most important first, empty when straight
+    fn classify(
+        &self,
+        measures: &PostureMeasures,
+        thresholds: &PostureThresholds,
+        current: &Posture,
+        hysteresis: f32,
+    ) -> Vec<PostureIssue>;
+
+    fn get_issues(
+        &self,
+        measured: &Measured,
+        thresholds: &PostureThresholds,
+        baseline: &PostureBaseline,
+        current: &Posture,
+        hysteresis: f32,
+    ) -> Vec<PostureIssue> {
+        match measured {
+            Err(posture) => vec![issue(posture.clone(), 1.0, 1.0)],
+            // Deviations from the neutral posture of the user, see `calibration`
+            Ok(measures) => self.classify(
+                &measures.relative_to(baseline),
+                thresholds,
+                current,
+                hysteresis,
+            ),
+        }
+    }
+
+    // Classifies the frame of a single front camera on its own, without hysteresis
+    fn classify_frame(
+        &self,
+        landmarks: PoseLandmarks,
+        thresholds: &PostureThresholds,
+        baseline: &PostureBaseline,
+    ) -> PostureUpdate {
+        let measured = measure_frame(
+            &landmarks,
+            CameraView::Front,
+            thresholds.min_visibility,
+            baseline,
+        );
+        let issues = self.get_issues(&measured, thresholds, baseline, &Posture::Unknown, 0.0);
+        build_update(landmarks, issues)
+    }
+}
+
+pub fn build_update(landmarks: PoseLandmarks, issues: Vec<PostureIssue>) -> PostureUpdate {
+    let posture = get_primary_posture(&issues);
+    let message = posture.get_posture_message();
+
+    PostureUpdate {
+        raw_posture: Some(posture.clone()),
+        posture,
+        message,
+        issues,
+        metrics: PostureMetrics::from_landmarks(&landmarks),
+        landmarks,
+    }
+}
+
+// The first issue is the one logged and notified, the posture is straight without any
+pub fn get_primary_posture(issues: &[PostureIssue]) -> Posture {
+    issues
+        .first()
+        .map(|issue| issue.posture.clone())
+        .unwrap_or(Posture::Straight)
+}
+
+pub fn load_classifier(
+    settings: &ClassifierSettings,
+) -> Result<Arc<dyn PostureClassifier>, String> {
+    match settings {
+        ClassifierSettings::Rules => Ok(Arc::new(RuleClassifier)),
+        ClassifierSettings::Linear { model_path } => {
+            Ok(Arc::new(LinearClassifier::load(Path::new(model_path))?))
+        }
+    }
+}
+
+// Postures that can't be true at the same time
+fn excludes(a: &Posture, b: &Posture) -> bool {
+    matches!(
+        (a, b),
+        (Posture::SlouchingBack, Posture::LeaningIn)
+            | (Posture::LeaningIn, Posture::SlouchingBack)
+            | (Posture::HeadTiltLeft, Posture::HeadTiltRight)
+            | (Posture::HeadTiltRight, Posture::HeadTiltLeft)
+            | (Posture::BodyTiltLeft, Posture::BodyTiltRight)
+            | (Posture::BodyTiltRight, Posture::BodyTiltLeft)
+    )
+}
+
+fn issue(posture: Posture, measure: f32, threshold: f32) -> PostureIssue {
+    PostureIssue {
+        posture,
+        severity: measure / threshold,
+    }
+}
+
+/*
+The default classifier, comparing every measure with its threshold in the order of importance:
+depth, head and body. Slouching back and leaning in exclude each other, as do the two sides
+of a tilt, but a slouch can come with a head and a body tilt.
+*/
+pub struct RuleClassifier;
+
+impl PostureClassifier for RuleClassifier {
+    fn classify(
+        &self,
+        measures: &PostureMeasures,
+        thresholds: &PostureThresholds,
+        current: &Posture,
+        hysteresis: f32,
+    ) -> Vec<PostureIssue> {
+        let thresholds = &thresholds.with_hysteresis(current, hysteresis);
+        let mut issues = Vec::new();
+
+        // Check slouching, seen from the front or from a side, each against its own threshold
+        let slouch = (measures.depth_gap / thresholds.slouch_depth)
+            .max(measures.side_gap / thresholds.side_slouch_gap);
+        let lean_in = (measures.depth_gap / thresholds.lean_in_depth)
+            .max(measures.side_gap / thresholds.side_lean_in_gap);
+        if slouch > 1.0 && measures.shoulder_depth > -thresholds.slouch_shoulder_depth {
+            issues.push(issue(Posture::SlouchingBack, slouch, 1.0));
+        } else if lean_in > 1.0 {
+            issues.push(issue(Posture::LeaningIn, lean_in, 1.0));
+        }
+
+        // Check head tilt
+        if measures.ear_slope > thresholds.head_tilt_slope {
+            issues.push(issue(
+                Posture::HeadTiltRight,
+                measures.ear_slope,
+                thresholds.head_tilt_slope,
+            ));
+        } else if measures.ear_slope < -thresholds.head_tilt_slope {
+            issues.push(issue(
+                Posture::HeadTiltLeft,
+                -measures.ear_slope,
+                thresholds.head_tilt_slope,
+            ));
+        }
+
+        // Check body tilt
+        if measures.shoulder_slope > thresholds.body_tilt_slope {
+            issues.push(issue(
+                Posture::BodyTiltRight,
+                measures.shoulder_slope,
+                thresholds.body_tilt_slope,
+            ));
+        } else if measures.shoulder_slope < -thresholds.body_tilt_slope {
+            issues.push(issue(
+                Posture::BodyTiltLeft,
+                -measures.shoulder_slope,
+                thresholds.body_tilt_slope,
+            ));
+        }
+
+        issues
+    }
+}
+
+/*
+One logistic regression per posture over the measures, trained outside the app, e.g.:
+{"min_probability": 0.5, "labels": [{"posture": "SlouchingBack", "bias": -6, "weights": {"depth_gap": 30}}]}
+A posture is an issue when its probability reaches `min_probability`, lowered by the hysteresis
+for the current posture, in the order of the labels. Of two postures that exclude each other,
+as for the rules, only the most likely is kept. Only `min_visibility` of the thresholds applies.
+*/
+#[derive(Debug, Clone, Deserialize)]
+pub struct LinearClassifier {
+    #[serde(default = "default_min_probability")]
+    min_probability: f32,
+    labels: Vec<LinearLabel>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct LinearLabel {
+    posture: Posture,
+    #[serde(default)]
+    bias: f32,
+    #[serde(default)]
+    weights: LinearWeights,
+}
+
+// Missing weights are 0, the measure doesn't count for the posture
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct LinearWeights {
+    depth_gap: f32,
+    shoulder_depth: f32,
+    ear_slope: f32,
+    shoulder_slope: f32,
+    side_gap: f32,
+}
+
+fn default_min_probability() -> f32 {
+    0.5
+}
+
+impl LinearClassifier {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let model = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read model {}: {}", path.display(), e))?;
+        Self::parse(&model).map_err(|e| format!("Invalid model {}: {}", path.display(), e))
+    }
+
+    pub fn parse(model: &str) -> Result<Self, String> {
+        let classifier: Self = serde_json::from_str(model).map_err(|e| e.to_string())?;
+
+        if !(classifier.min_probability > 0.0 && classifier.min_probability < 1.0) {
+            return Err("min_probability must be between 0 and 1".to_string());
+        }
+        if classifier.labels.is_empty() {
+            return Err("no labels".to_string());
+        }
+        for label in &classifier.labels {
+            if matches!(
+                label.posture,
+                Posture::ShouldersNotVisible
+                    | Posture::HeadNotVisible
+                    | Posture::Straight
+                    | Posture::Stale
+                    | Posture::Paused
+                    | Posture::Unknown
+            ) {
+                return Err(format!(
+                    "{} can't be a label",
+                    label.posture.get_posture_value()
+                ));
+            }
+
+            let weights = &label.weights;
+            let finite = [
+                label.bias,
+                weights.depth_gap,
+                weights.shoulder_depth,
+                weights.ear_slope,
+                weights.shoulder_slope,
+                weights.side_gap,
+            ]
+            .iter()
+            .all(|value| value.is_finite());
+            if !finite {
+                return Err(format!(
+                    "weights of {} must be finite",
+                    label.posture.get_posture_value()
+                ));
+            }
+        }
+
+        Ok(classifier)
+    }
+}
+
+impl PostureClassifier for LinearClassifier {
+    fn classify(
+        &self,
+        measures: &PostureMeasures,
+        _: &PostureThresholds,
+        current: &Posture,
+        hysteresis: f32,
+    ) -> Vec<PostureIssue> {
+        let candidates: Vec<(PostureIssue, f32)> = self
+            .labels
+            .iter()
+            .filter_map(|label| {
+                let weights = &label.weights;
+                let score = label.bias
+                    + weights.depth_gap * measures.depth_gap
+                    + weights.shoulder_depth * measures.shoulder_depth
+                    + weights.ear_slope * measures.ear_slope
+                    + weights.shoulder_slope * measures.shoulder_slope
+                    + weights.side_gap * measures.side_gap;
+                let probability = 1.0 / (1.0 + (-score).exp());
+
+                let min_probability =
+                    if label.posture.get_posture_value() == current.get_posture_value() {
+                        self.min_probability * (1.0 - hysteresis)
+                    } else {
+                        self.min_probability
+                    };
+                (probability >= min_probability).then(|| {
+                    (
+                        issue(label.posture.clone(), probability, min_probability),
+                        probability,
+                    )
+                })
+            })
+            .collect();
+
+        // An earlier label wins a tie
+        candidates
+            .iter()
+            .enumerate()
+            .filter(|(index, (issue, probability))| {
+                !candidates
+                    .iter()
+                    .enumerate()
+                    .any(|(other_index, (other, other_probability))| {
+                        excludes(&issue.posture, &other.posture)
+                            && (other_probability > probability
+                                || (other_probability == probability && other_index < *index))
+                    })
+            })
+            .map(|(_, (issue, _))| issue.clone())
+            .collect()
+    }
+}
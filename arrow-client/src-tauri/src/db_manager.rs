@@ -1,4 +1,5 @@
-use rusqlite::{Connection, Result as SqlResult};
+use crate::events::PostureIssue;
+use rusqlite::{params, Connection, Result as SqlResult};
 use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::fs;
@@ -68,6 +69,17 @@ impl DbManager {
             [],
         )?;
 
+        // Every issue of the frame that changed the posture, the posture of the event is the first
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS posture_issues (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                event_id INTEGER NOT NULL REFERENCES posture_events(id),
+                posture TEXT NOT NULL,
+                severity REAL NOT NULL
+            )",
+            [],
+        )?;
+
         Ok(())
     }
 
@@ -93,14 +105,65 @@ impl DbManager {
         Ok(())
     }
 
-    pub fn log_posture_change(&self, current_posture: &str, last_posture: &str) -> SqlResult<()> {
-        self.conn.execute(
+    pub fn log_posture_change(
+        &self,
+        current_posture: &str,
+        last_posture: &str,
+        issues: &[PostureIssue],
+    ) -> SqlResult<()> {
+        let transaction = self.conn.unchecked_transaction()?;
+        transaction.execute(
             "INSERT INTO posture_events 
              (timestamp, event_type, posture, previous_posture)
              VALUES (datetime('now'), 'CHANGE', ?, ?)",
             [current_posture, last_posture],
         )?;
 
+        let event_id = transaction.last_insert_rowid();
+        for issue in issues {
+            transaction.execute(
+                "INSERT INTO posture_issues (event_id, posture, severity) VALUES (?, ?, ?)",
+                params![event_id, issue.posture.get_posture_value(), issue.severity],
+            )?;
+        }
+
+        transaction.commit()
+    }
+
+    // Issues logged with the latest posture change, with their severity
+    pub fn get_last_change_issues(&self) -> SqlResult<Vec<(String, f32)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT posture, severity
+                FROM posture_issues
+                WHERE event_id = (SELECT MAX(id) FROM posture_events WHERE event_type = 'CHANGE')
+                ORDER BY id",
+        )?;
+
+        let issues = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        issues.collect()
+    }
+
+    // The stream went stale: the time until the next CHANGE is attributed to the STALE posture
+    pub fn log_stream_stale(&self, last_posture: &str) -> SqlResult<()> {
+        self.conn.execute(
+            "INSERT INTO posture_events 
+             (timestamp, event_type, posture, previous_posture)
+             VALUES (datetime('now'), 'STALE', 'STALE', ?)",
+            [last_posture],
+        )?;
+
+        Ok(())
+    }
+
+    // Monitoring was paused or stopped: the time until the next CHANGE is attributed to PAUSED
+    pub fn log_monitoring_paused(&self, last_posture: &str) -> SqlResult<()> {
+        self.conn.execute(
+            "INSERT INTO posture_events 
+             (timestamp, event_type, posture, previous_posture)
+             VALUES (datetime('now'), 'PAUSE', 'PAUSED', ?)",
+            [last_posture],
+        )?;
+
         Ok(())
     }
 
@@ -170,17 +233,20 @@ impl DbManager {
             let date: String = date_stmt.query_row([], |row| row.get(0))?;
             
             // Calculate total session time and posture breakdowns for this day
+            // Each interval belongs to the posture held before the event that ends it,
+            // time without data from the server or while paused is not counted as good or bad posture
             let mut stats_stmt = self.conn.prepare(&format!(
                 "SELECT 
-                    logs.posture,
+                    logs.previous_posture,
                     SUM((julianday(logs.timestamp) - julianday(e2.timestamp)) * 86400.0) as total_duration
                 FROM posture_events logs
                 JOIN posture_events e2 ON logs.id = e2.id + 1
                 WHERE logs.timestamp >= {} AND logs.timestamp < {}
                 AND ((julianday(logs.timestamp) - julianday(e2.timestamp)) * 86400.0) > 3
                 AND logs.event_type != 'START'
-                GROUP BY logs.posture
-                ORDER BY logs.posture", date_start, date_end
+                AND logs.previous_posture NOT IN ('STALE', 'PAUSED')
+                GROUP BY logs.previous_posture
+                ORDER BY logs.previous_posture", date_start, date_end
             ))?;
             
             let posture_durations = stats_stmt.query_map([], |row| {
@@ -219,7 +285,7 @@ impl DbManager {
         Ok(WeeklyStats { days })
     }
 
-    fn get_app_data_dir() -> PathBuf {
+    pub fn get_app_data_dir() -> PathBuf {
         let mut app_dir = dirs::data_local_dir().unwrap_or_else(|| PathBuf::from("."));
         app_dir.push("PostureMonitor");
         app_dir
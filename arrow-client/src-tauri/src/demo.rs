@@ -0,0 +1,427 @@
+use crate::events::{ConnectionStatus, Point3D};
+use crate::landmarks::{Landmark, PoseLandmarks};
+use crate::postures::Posture;
+use crate::protocol::FrameMetadata;
+use crate::settings::{DemoScenario, DemoSettings, Settings, DEMO_SOURCE};
+use crate::source::{FrameSink, PostureSource};
+use std::f32::consts::PI;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+use tokio::time::{interval, Duration, Instant};
+
+// Time taken to move from one posture to the next
+const TRANSITION: Duration = Duration::from_millis(1500);
+// Slouching creeps in much more slowly than a deliberate movement
+const SLOUCH_TRANSITION: Duration = Duration::from_secs(30);
+// Duration of the head tilts of the frequent head tilts scenario
+const HEAD_TILT: Duration = Duration::from_secs(3);
+
+const CYCLE: [Posture; 18] = [
+    Posture::Straight,
+    Posture::SlouchingBack,
+    Posture::Straight,
+    Posture::LeaningIn,
+    Posture::Straight,
+    Posture::HeadTiltLeft,
+    Posture::Straight,
+    Posture::HeadTiltRight,
+    Posture::Straight,
+    Posture::BodyTiltLeft,
+    Posture::Straight,
+    Posture::BodyTiltRight,
+    Posture::Straight,
+    Posture::ShouldersNotVisible,
+    Posture::Straight,
+    Posture::HeadNotVisible,
+    Posture::Straight,
+    Posture::Stale,
+];
+
+// The landmarks used by the classification, plus the nose
+#[derive(Debug, Clone)]
+struct Pose {
+    nose: Point3D,
+    left_ear: Point3D,
+    right_ear: Point3D,
+    left_shoulder: Point3D,
+    right_shoulder: Point3D,
+}
+
+impl Pose {
+    /*
+    Landmarks a person sitting in the given posture in front of the camera would produce.
+    Stale and Unknown have no pose of their own and sit straight.
+    */
+    fn for_posture(posture: &Posture) -> Self {
+        let point = |x: f32, y: f32, z: f32| Point3D {
+            x,
+            y,
+            z,
+            visibility: 0.99,
+        };
+        let mut pose = Pose {
+            nose: point(0.5, 0.28, -0.1),
+            left_ear: point(0.58, 0.3, 0.0),
+            right_ear: point(0.42, 0.3, 0.0),
+            left_shoulder: point(0.68, 0.55, 0.0),
+            right_shoulder: point(0.32, 0.55, 0.0),
+        };
+
+        match posture {
+            Posture::SlouchingBack => {
+                pose.left_shoulder.z = 0.3;
+                pose.right_shoulder.z = 0.3;
+                pose.nose.y = 0.33;
+                pose.left_ear.y = 0.34;
+                pose.right_ear.y = 0.34;
+            }
+            Posture::LeaningIn => {
+                pose.nose.z = -0.9;
+                pose.left_ear.z = -0.8;
+                pose.right_ear.z = -0.8;
+                pose.left_shoulder.z = -0.4;
+                pose.right_shoulder.z = -0.4;
+            }
+            Posture::HeadTiltLeft => {
+                pose.left_ear.y = 0.27;
+                pose.right_ear.y = 0.33;
+                pose.nose.x = 0.48;
+            }
+            Posture::HeadTiltRight => {
+                pose.left_ear.y = 0.33;
+                pose.right_ear.y = 0.27;
+                pose.nose.x = 0.52;
+            }
+            Posture::BodyTiltLeft => {
+                pose.left_shoulder.y = 0.52;
+                pose.right_shoulder.y = 0.58;
+            }
+            Posture::BodyTiltRight => {
+                pose.left_shoulder.y = 0.58;
+                pose.right_shoulder.y = 0.52;
+            }
+            Posture::ShouldersNotVisible => {
+                pose.left_shoulder.visibility = 0.4;
+                pose.right_shoulder.visibility = 0.4;
+            }
+            Posture::HeadNotVisible => {
+                pose.nose.visibility = 0.3;
+                pose.left_ear.visibility = 0.4;
+                pose.right_ear.visibility = 0.4;
+            }
+            Posture::Straight | Posture::Stale | Posture::Paused | Posture::Unknown => {}
+        }
+
+        pose
+    }
+
+    fn get_points_mut(&mut self) -> [&mut Point3D; 5] {
+        [
+            &mut self.nose,
+            &mut self.left_ear,
+            &mut self.right_ear,
+            &mut self.left_shoulder,
+            &mut self.right_shoulder,
+        ]
+    }
+
+    fn blend(from: &Pose, to: &Pose, progress: f32) -> Pose {
+        let lerp = |a: &Point3D, b: &Point3D| Point3D {
+            x: a.x + (b.x - a.x) * progress,
+            y: a.y + (b.y - a.y) * progress,
+            z: a.z + (b.z - a.z) * progress,
+            visibility: a.visibility + (b.visibility - a.visibility) * progress,
+        };
+
+        Pose {
+            nose: lerp(&from.nose, &to.nose),
+            left_ear: lerp(&from.left_ear, &to.left_ear),
+            right_ear: lerp(&from.right_ear, &to.right_ear),
+            left_shoulder: lerp(&from.left_shoulder, &to.left_shoulder),
+            right_shoulder: lerp(&from.right_shoulder, &to.right_shoulder),
+        }
+    }
+
+    fn into_landmarks(self) -> PoseLandmarks {
+        let mut landmarks = PoseLandmarks::new();
+        landmarks.insert(Landmark::Nose, self.nose);
+        landmarks.insert(Landmark::LeftEar, self.left_ear);
+        landmarks.insert(Landmark::RightEar, self.right_ear);
+        landmarks.insert(Landmark::LeftShoulder, self.left_shoulder);
+        landmarks.insert(Landmark::RightShoulder, self.right_shoulder);
+        landmarks
+    }
+}
+
+// Where the scenario is at a given time
+struct Segment {
+    posture: Posture,
+    previous: Posture,
+    // Time since the scenario switched to `posture`
+    elapsed: Duration,
+    transition: Duration,
+}
+
+// Ears or shoulders out of view until `until`
+struct Dropout {
+    until: Duration,
+    ears: bool,
+}
+
+/*
+Generates the frames of a demo scenario.
+Deterministic for a given seed, so scenarios can be checked in tests.
+*/
+pub struct DemoGenerator {
+    settings: DemoSettings,
+    rng: fastrand::Rng,
+    dropout: Option<Dropout>,
+}
+
+impl DemoGenerator {
+    pub fn new(settings: DemoSettings, seed: u64) -> Self {
+        Self {
+            settings,
+            rng: fastrand::Rng::with_seed(seed),
+            dropout: None,
+        }
+    }
+
+    // Frame at `time` in scenario time, None while the scenario plays a frozen stream
+    pub fn frame_at(&mut self, time: Duration) -> Option<PoseLandmarks> {
+        let segment = self.get_segment(time);
+        if matches!(segment.posture, Posture::Stale) {
+            return None;
+        }
+
+        // Eased so movements start and end smoothly
+        let progress = (segment.elapsed.as_secs_f32() / segment.transition.as_secs_f32()).min(1.0);
+        let progress = (1.0 - (progress * PI).cos()) / 2.0;
+        let mut pose = Pose::blend(
+            &Pose::for_posture(&segment.previous),
+            &Pose::for_posture(&segment.posture),
+            progress,
+        );
+
+        // Slow swaying, nobody sits perfectly still
+        let seconds = time.as_secs_f32();
+        let sway_x = 0.01 * (seconds * 0.4).sin();
+        let sway_y = 0.005 * (seconds * 0.25).sin();
+
+        self.update_dropout(time);
+        let (ears_hidden, shoulders_hidden) = match &self.dropout {
+            Some(dropout) => (dropout.ears, !dropout.ears),
+            None => (false, false),
+        };
+
+        let noise = self.settings.noise;
+        let [nose, left_ear, right_ear, left_shoulder, right_shoulder] = pose.get_points_mut();
+        for (point, hidden) in [
+            (nose, ears_hidden),
+            (left_ear, ears_hidden),
+            (right_ear, ears_hidden),
+            (left_shoulder, shoulders_hidden),
+            (right_shoulder, shoulders_hidden),
+        ] {
+            point.x += sway_x + self.get_noise(noise);
+            point.y += sway_y + self.get_noise(noise);
+            point.z += self.get_noise(noise);
+
+            if hidden {
+                point.visibility = 0.2 + self.rng.f32() * 0.3;
+            }
+            point.visibility = (point.visibility - self.rng.f32() * 0.03).clamp(0.0, 1.0);
+        }
+
+        Some(pose.into_landmarks())
+    }
+
+    fn get_segment(&self, time: Duration) -> Segment {
+        let seconds = time.as_secs();
+
+        match &self.settings.scenario {
+            DemoScenario::Cycle { hold_secs } => {
+                let hold_secs = (*hold_secs).max(1);
+                let index = (seconds / hold_secs) as usize % CYCLE.len();
+                let previous = (index + CYCLE.len() - 1) % CYCLE.len();
+
+                Segment {
+                    posture: CYCLE[index].clone(),
+                    previous: CYCLE[previous].clone(),
+                    elapsed: time - Duration::from_secs(seconds - seconds % hold_secs),
+                    transition: TRANSITION,
+                }
+            }
+            DemoScenario::SlouchAfter { straight_minutes } => {
+                let straight = Duration::from_secs(straight_minutes * 60);
+                match time.checked_sub(straight) {
+                    Some(elapsed) => Segment {
+                        posture: Posture::SlouchingBack,
+                        previous: Posture::Straight,
+                        elapsed,
+                        transition: SLOUCH_TRANSITION,
+                    },
+                    None => Segment {
+                        posture: Posture::Straight,
+                        previous: Posture::Straight,
+                        elapsed: time,
+                        transition: TRANSITION,
+                    },
+                }
+            }
+            DemoScenario::FrequentHeadTilts { interval_secs } => {
+                let interval = Duration::from_secs((*interval_secs).max(1));
+                let period = (time.as_secs_f64() / interval.as_secs_f64()) as u32;
+                let phase = time - interval * period;
+                let tilt = |period: u32| {
+                    if period.is_multiple_of(2) {
+                        Posture::HeadTiltLeft
+                    } else {
+                        Posture::HeadTiltRight
+                    }
+                };
+
+                // Each period ends with a head tilt
+                let straight = interval.saturating_sub(HEAD_TILT);
+                if phase < straight {
+                    Segment {
+                        posture: Posture::Straight,
+                        previous: if period == 0 {
+                            Posture::Straight
+                        } else {
+                            tilt(period - 1)
+                        },
+                        elapsed: phase,
+                        transition: TRANSITION,
+                    }
+                } else {
+                    Segment {
+                        posture: tilt(period),
+                        previous: Posture::Straight,
+                        elapsed: phase - straight,
+                        transition: TRANSITION,
+                    }
+                }
+            }
+            DemoScenario::Steady { posture } => Segment {
+                posture: posture.clone(),
+                previous: posture.clone(),
+                elapsed: time,
+                transition: TRANSITION,
+            },
+        }
+    }
+
+    fn update_dropout(&mut self, time: Duration) {
+        if self
+            .dropout
+            .as_ref()
+            .is_some_and(|dropout| time >= dropout.until)
+        {
+            self.dropout = None;
+        }
+        if self.dropout.is_some() {
+            return;
+        }
+
+        let probability = self.settings.dropouts_per_minute / 60.0 / self.settings.frame_rate;
+        if self.rng.f32() < probability {
+            self.dropout = Some(Dropout {
+                until: time + Duration::from_secs_f32(0.5 + self.rng.f32() * 2.0),
+                ears: self.rng.bool(),
+            });
+        }
+    }
+
+    // Triangular noise, small deviations are more likely than large ones
+    fn get_noise(&mut self, amplitude: f32) -> f32 {
+        (self.rng.f32() - self.rng.f32()) * amplitude
+    }
+}
+
+// Source playing a demo scenario, in place of a camera
+pub struct DemoSource {
+    settings: DemoSettings,
+    app_settings: Arc<Mutex<Settings>>,
+}
+
+impl DemoSource {
+    pub fn new(settings: DemoSettings, app_settings: Arc<Mutex<Settings>>) -> Self {
+        Self {
+            settings,
+            app_settings,
+        }
+    }
+
+    fn get_scenario_description(&self) -> String {
+        match &self.settings.scenario {
+            DemoScenario::Cycle { hold_secs } => {
+                format!("every posture in turn, {}s each", hold_secs)
+            }
+            DemoScenario::SlouchAfter { straight_minutes } => {
+                format!("slouching after {} minutes", straight_minutes)
+            }
+            DemoScenario::FrequentHeadTilts { interval_secs } => {
+                format!("head tilt every {}s", interval_secs)
+            }
+            DemoScenario::Steady { posture } => posture.get_posture_message(),
+        }
+    }
+}
+
+impl PostureSource for DemoSource {
+    fn get_name(&self) -> &str {
+        DEMO_SOURCE
+    }
+
+    async fn run(self, sink: FrameSink) {
+        sink.report_status(ConnectionStatus {
+            connected: true,
+            message: format!("Demo mode: {}", self.get_scenario_description()),
+            server_address: DEMO_SOURCE.to_string(),
+            ..Default::default()
+        })
+        .await;
+
+        let speed = self.settings.speed;
+        let mut generator = DemoGenerator::new(self.settings.clone(), fastrand::u64(..));
+        let mut ticker = interval(Duration::from_secs_f32(1.0 / self.settings.frame_rate));
+        let started = Instant::now();
+        // Set while the scenario plays a frozen stream
+        let mut silent_since: Option<Instant> = None;
+        let mut stale_reported = false;
+        let mut seq = 0;
+
+        loop {
+            ticker.tick().await;
+
+            match generator.frame_at(started.elapsed().mul_f32(speed)) {
+                Some(landmarks) => {
+                    silent_since = None;
+                    stale_reported = false;
+                    seq += 1;
+                    let metadata = FrameMetadata {
+                        seq: Some(seq),
+                        captured_at: SystemTime::now()
+                            .duration_since(UNIX_EPOCH)
+                            .map(|duration| duration.as_secs_f64() * 1000.0)
+                            .ok(),
+                    };
+                    sink.push_frame(landmarks, metadata).await;
+                }
+                None => {
+                    // Reported like a server that stopped sending frames
+                    let silence = silent_since.get_or_insert_with(Instant::now).elapsed();
+                    let frame_timeout = Duration::from_millis(
+                        self.app_settings.lock().await.stream.frame_timeout_ms,
+                    );
+                    if !stale_reported && silence >= frame_timeout {
+                        stale_reported = true;
+                        sink.mark_stale(silence).await;
+                    }
+                }
+            }
+        }
+    }
+}
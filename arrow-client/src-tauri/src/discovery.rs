@@ -0,0 +1,276 @@
+use crate::events::{DiscoveredServer, DiscoveredServersUpdate};
+use crate::protocol::{self, ServerBeacon};
+use crate::settings::{DiscoverySettings, ServerAddress, Settings, Transport};
+use crate::tcp_client::TcpClient;
+use std::collections::BTreeMap;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter};
+use tokio::net::UdpSocket;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tokio::time::{interval, sleep, Duration, Instant};
+
+// How often the servers that stopped announcing themselves are looked for
+const EXPIRY_CHECK: Duration = Duration::from_secs(1);
+// Larger datagrams are truncated and rejected
+const MAX_BEACON_SIZE: usize = 2048;
+
+/*
+Listens for the beacons of the posture servers on the local network, see `protocol`.
+Beacons can be broadcast, sent to the multicast group of the settings or straight to the client,
+a server is known by the address it sends from and the port it announces.
+When the server named in `auto_connect` is announced, the primary source switches to it.
+Every change of the list is emitted as a `discovered-servers` event.
+*/
+pub struct ServerDiscovery {
+    listener: Listener,
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+#[derive(Clone)]
+struct Listener {
+    app_handle: AppHandle,
+    settings: Arc<Mutex<Settings>>,
+    tcp_client: Arc<Mutex<Option<TcpClient>>>,
+    servers: Arc<Mutex<DiscoveredServers>>,
+}
+
+// Servers announced recently, by the address they listen on
+#[derive(Debug, Default)]
+pub struct DiscoveredServers {
+    servers: BTreeMap<String, (DiscoveredServer, Instant)>,
+}
+
+impl DiscoveredServers {
+    // Returns the server and whether it is new or changed since its last beacon
+    pub fn record(
+        &mut self,
+        beacon: ServerBeacon,
+        host: IpAddr,
+        now: Instant,
+    ) -> (DiscoveredServer, bool) {
+        let server = DiscoveredServer {
+            address: ServerAddress {
+                transport: Transport::Tcp,
+                host: host.to_string(),
+                port: beacon.port,
+                tls: beacon.tls,
+                ..ServerAddress::default()
+            },
+            incompatibility: protocol::check_protocol_version(beacon.protocol_version).err(),
+            server_name: beacon.server_name,
+            server_version: beacon.server_version,
+            protocol_version: beacon.protocol_version,
+            last_seen_ms: 0,
+        };
+
+        let key = server.address.to_string();
+        let changed = self
+            .servers
+            .get(&key)
+            .is_none_or(|(known, _)| *known != server);
+        self.servers.insert(key, (server.clone(), now));
+
+        (server, changed)
+    }
+
+    // Forgets the servers not announced within `expiry`, returns true if there were any
+    pub fn expire(&mut self, now: Instant, expiry: Duration) -> bool {
+        let count = self.servers.len();
+        self.servers
+            .retain(|_, (_, last_seen)| now.duration_since(*last_seen) < expiry);
+        self.servers.len() != count
+    }
+
+    pub fn clear(&mut self) {
+        self.servers.clear();
+    }
+
+    pub fn get_servers(&self, now: Instant) -> Vec<DiscoveredServer> {
+        self.servers
+            .values()
+            .map(|(server, last_seen)| DiscoveredServer {
+                last_seen_ms: now.duration_since(*last_seen).as_millis() as u64,
+                ..server.clone()
+            })
+            .collect()
+    }
+}
+
+impl ServerDiscovery {
+    pub fn new(
+        app_handle: AppHandle,
+        settings: Arc<Mutex<Settings>>,
+        tcp_client: Arc<Mutex<Option<TcpClient>>>,
+    ) -> Self {
+        Self {
+            listener: Listener {
+                app_handle,
+                settings,
+                tcp_client,
+                servers: Arc::new(Mutex::new(DiscoveredServers::default())),
+            },
+            task: Mutex::new(None),
+        }
+    }
+
+    // Listens with these settings, the servers found so far are forgotten
+    pub async fn start(&self, discovery: DiscoverySettings) -> Result<(), String> {
+        let mut task = self.task.lock().await;
+        if let Some(handle) = task.take() {
+            handle.abort();
+            let _ = handle.await;
+        }
+
+        self.listener.servers.lock().await.clear();
+        self.listener.report_servers().await;
+        if !discovery.enabled {
+            return Ok(());
+        }
+
+        let socket = bind_socket(&discovery).await.map_err(|e| {
+            format!(
+                "Failed to listen for servers on port {}: {}",
+                discovery.port, e
+            )
+        })?;
+        *task = Some(tokio::spawn(self.listener.clone().run(socket, discovery)));
+
+        Ok(())
+    }
+
+    pub async fn stop(&self) {
+        if let Some(handle) = self.task.lock().await.take() {
+            handle.abort();
+            let _ = handle.await;
+        }
+    }
+
+    pub async fn get_servers(&self) -> Vec<DiscoveredServer> {
+        self.listener
+            .servers
+            .lock()
+            .await
+            .get_servers(Instant::now())
+    }
+}
+
+impl Listener {
+    async fn run(self, socket: UdpSocket, discovery: DiscoverySettings) {
+        let expiry = Duration::from_millis(discovery.expiry_ms);
+        let mut expiry_check = interval(EXPIRY_CHECK);
+
+        loop {
+            tokio::select! {
+                received = receive_beacon(&socket) => match received {
+                    Ok((beacon, sender)) => {
+                        self.handle_beacon(beacon, sender.ip(), &discovery).await;
+                    }
+                    Err(e) => {
+                        // e.g. an ICMP error reported on the socket, keeps listening
+                        eprintln!("Failed to receive server beacon: {}", e);
+                        sleep(EXPIRY_CHECK).await;
+                    }
+                },
+                _ = expiry_check.tick() => {
+                    let expired = self.servers.lock().await.expire(Instant::now(), expiry);
+                    if expired {
+                        self.report_servers().await;
+                    }
+                }
+            }
+        }
+    }
+
+    async fn handle_beacon(
+        &self,
+        beacon: ServerBeacon,
+        host: IpAddr,
+        discovery: &DiscoverySettings,
+    ) {
+        let auto_connect = discovery
+            .auto_connect
+            .as_ref()
+            .is_some_and(|name| *name == beacon.server_name);
+        let (server, changed) = self
+            .servers
+            .lock()
+            .await
+            .record(beacon, host, Instant::now());
+
+        if changed {
+            self.report_servers().await;
+        }
+        if auto_connect && server.incompatibility.is_none() {
+            self.connect(server.address).await;
+        }
+    }
+
+    /*
+    Switches the primary source to the announced address for this run, unless it is already
+    connected to it. The configured server is kept in the settings, and its token and pinned
+    certificate are never sent to an address that only a beacon vouches for.
+    */
+    async fn connect(&self, announced: ServerAddress) {
+        let configured = self.settings.lock().await.server.clone();
+        if !can_auto_connect(&configured, &announced) {
+            eprintln!(
+                "Not connecting to discovered server at {}, it is less secure than {}",
+                announced, configured
+            );
+            return;
+        }
+
+        if let Some(tcp_client) = self.tcp_client.lock().await.as_ref() {
+            if tcp_client.get_server_address() != announced {
+                println!("Connecting to discovered server at {}", announced);
+                tcp_client.set_server_address(announced);
+            }
+        }
+    }
+
+    async fn report_servers(&self) {
+        let servers = self.servers.lock().await.get_servers(Instant::now());
+        let _ = self
+            .app_handle
+            .emit("discovered-servers", DiscoveredServersUpdate { servers });
+    }
+}
+
+// Socket receiving the beacons, a `port` of 0 picks a free one
+pub async fn bind_socket(discovery: &DiscoverySettings) -> io::Result<UdpSocket> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, discovery.port)).await?;
+
+    if let Some(group) = discovery.multicast_group {
+        // Broadcast beacons still get through, e.g. when no interface supports multicast
+        if let Err(e) = socket.join_multicast_v4(group, Ipv4Addr::UNSPECIFIED) {
+            eprintln!("Failed to join discovery group {}: {}", group, e);
+        }
+    }
+
+    Ok(socket)
+}
+
+// Waits for the next beacon, other datagrams sent to the port are skipped
+pub async fn receive_beacon(socket: &UdpSocket) -> io::Result<(ServerBeacon, SocketAddr)> {
+    let mut buffer = [0u8; MAX_BEACON_SIZE];
+
+    loop {
+        let (length, sender) = socket.recv_from(&mut buffer).await?;
+        if let Some(beacon) = protocol::parse_beacon(&buffer[..length]) {
+            return Ok((beacon, sender));
+        }
+    }
+}
+
+/*
+Whether the primary source may leave the configured server for an announced one.
+Anyone on the network can send a beacon, so the announced server must be at least as private
+as the configured one, and a pinned certificate only ever vouches for the configured server.
+*/
+pub fn can_auto_connect(configured: &ServerAddress, announced: &ServerAddress) -> bool {
+    configured.pinned_certificate.is_none()
+        && (announced.is_confidential() || !configured.is_confidential())
+}
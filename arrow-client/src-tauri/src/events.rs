@@ -1,12 +1,29 @@
+use crate::calibration::PostureBaseline;
 use crate::db_manager::PostureLog;
+use crate::landmarks::{Landmark, PoseLandmarks};
 use crate::postures::Posture;
+use crate::protocol::ServerInfo;
+use crate::settings::ServerAddress;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PostureUpdate {
+    // Accepted once it lasted long enough, see `smoothing`
     pub posture: Posture,
     pub message: String,
+    // What this frame alone was classified as, for debugging
+    pub raw_posture: Option<Posture>,
+    // Every issue of this frame, the most important first, empty when straight
+    pub issues: Vec<PostureIssue>,
     pub metrics: Option<PostureMetrics>,
+    pub landmarks: PoseLandmarks,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PostureIssue {
+    pub posture: Posture,
+    // How far the measure is past its threshold, 1 at the threshold
+    pub severity: f32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,6 +34,18 @@ pub struct PostureMetrics {
     pub right_shoulder: Point3D,
 }
 
+impl PostureMetrics {
+    // Only available when the frame contains both ears and both shoulders
+    pub fn from_landmarks(landmarks: &PoseLandmarks) -> Option<Self> {
+        Some(Self {
+            left_ear: landmarks.get(Landmark::LeftEar)?.clone(),
+            right_ear: landmarks.get(Landmark::RightEar)?.clone(),
+            left_shoulder: landmarks.get(Landmark::LeftShoulder)?.clone(),
+            right_shoulder: landmarks.get(Landmark::RightShoulder)?.clone(),
+        })
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Point3D {
     pub x: f32,
@@ -25,10 +54,172 @@ pub struct Point3D {
     pub visibility: f32,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/*
+Reported whenever one of the sources connects, disconnects or goes silent.
+`connected` is true while at least one source is connected, the other top level fields
+describe the source the status is about and `sources` holds the health of all of them.
+*/
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ConnectionStatus {
     pub connected: bool,
     pub message: String,
+    pub server_address: String,
+    // Number of consecutive failed connection attempts
+    pub attempt: u32,
+    // Unix timestamp in milliseconds of the next connection attempt, if one is scheduled
+    pub next_retry_at: Option<u64>,
+    // Kind of the error that caused the last disconnection or failed attempt
+    pub last_error_kind: Option<String>,
+    pub sources: Vec<SourceStatus>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SourceStatus {
+    pub name: String,
+    pub server_address: String,
+    pub connected: bool,
+    // Connected, but no frame received within the frame timeout
+    pub stale: bool,
+    pub message: String,
+    pub attempt: u32,
+    // Unix timestamp in milliseconds of the last frame received from this source
+    pub last_frame_at: Option<u64>,
+    pub server_info: Option<ServerInfo>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MonitoringState {
+    Stopped,
+    Running,
+    // Still connected, the servers are asked to release their camera and frames are ignored
+    Paused,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MonitoringStateUpdate {
+    pub state: MonitoringState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServerProcessState {
+    // The server is started separately
+    Disabled,
+    Starting,
+    Running,
+    // Exited, waiting before the next restart
+    Restarting,
+    // Kept exiting, no more restarts
+    Failed,
+    Stopped,
+}
+
+// Health of the server process supervised by the app
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerProcessStatus {
+    pub state: ServerProcessState,
+    pub pid: Option<u32>,
+    // Restarts since the server last ran long enough to be considered healthy
+    pub restarts: u32,
+    // Exit status of the last run, or why it could not be started
+    pub last_exit: Option<String>,
+    // Last line written to stderr, usually the error a crashing server died with
+    pub last_error_output: Option<String>,
+    pub message: String,
+}
+
+// A posture server announced on the local network
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiscoveredServer {
+    pub server_name: String,
+    pub server_version: String,
+    pub protocol_version: u32,
+    // Can be passed as is to set_server_address
+    pub address: ServerAddress,
+    // Why the client cannot connect to it, e.g. a newer protocol version
+    pub incompatibility: Option<String>,
+    // Time since its last beacon
+    pub last_seen_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiscoveredServersUpdate {
+    pub servers: Vec<DiscoveredServer>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CalibrationProgress {
+    // Between 0 and 1
+    pub progress: f32,
+    // Frames measured so far
+    pub samples: u32,
+    pub elapsed_ms: u64,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CalibrationResult {
+    pub success: bool,
+    pub message: String,
+    // The new baseline, the previous one is kept when the calibration fails
+    pub baseline: Option<PostureBaseline>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamStale {
+    // Time since the last frame was received
+    pub silence_ms: u64,
+    pub previous_posture: String,
+}
+
+// Line counters for the current connection of a source, reset on every new connection
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StreamDiagnostics {
+    pub source: String,
+    pub server_address: String,
+    pub good_lines: u64,
+    pub rejected_lines: u64,
+    pub unknown_format_lines: u64,
+    pub last_error: Option<String>,
+}
+
+impl StreamDiagnostics {
+    // Zeroed counters for a connection that just opened
+    pub fn new(source: &str, address: &ServerAddress) -> Self {
+        Self {
+            source: source.to_string(),
+            server_address: address.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+// Frame statistics of a source, emitted periodically while it sends frames
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StreamStats {
+    pub source: String,
+    pub frames: u64,
+    // Over the last few seconds
+    pub frames_per_second: f32,
+    // Frames missing from the sequence numbers, and in how many gaps
+    pub dropped_frames: u64,
+    pub gaps: u64,
+    pub out_of_order_frames: u64,
+    // From the capture to the posture update, over the last frames with a capture timestamp
+    pub latency_ms: Option<f32>,
+    pub max_latency_ms: Option<f32>,
+}
+
+// Queue between two stages of the pipeline
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QueueStats {
+    pub name: String,
+    // Items waiting for the next stage
+    pub depth: usize,
+    pub capacity: usize,
+    // Items dropped because the next stage was falling behind
+    pub dropped: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -41,4 +232,4 @@ pub struct NotificationEvent {
     pub posture: String,
     pub message: String,
     pub is_good_posture: bool,
-}
\ No newline at end of file
+}
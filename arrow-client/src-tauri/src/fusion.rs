@@ -0,0 +1,261 @@
+use crate::calibration::{PostureBaseline, PostureMeasures};
+use crate::events::Point3D;
+use crate::landmarks::{Landmark, PoseLandmarks};
+use crate::postures::Posture;
+use crate::settings::CameraView;
+use std::collections::BTreeMap;
+use tokio::time::{Duration, Instant};
+
+struct SourceFrame {
+    landmarks: PoseLandmarks,
+    weight: f32,
+    view: CameraView,
+    received_at: Instant,
+}
+
+// Measures of the posture, or the visibility issue that prevented measuring it
+pub type Measured = Result<PostureMeasures, Posture>;
+
+// What the sources saw together, ready for classification
+pub struct FusedFrame {
+    // Frame of the most trusted source, preferring a front camera, shown to the user
+    pub landmarks: PoseLandmarks,
+    pub measures: Measured,
+}
+
+/*
+What a single camera can measure, a side camera only sees how far forward the head is.
+The two are in different units, so they are kept apart rather than averaged together.
+*/
+struct ViewMeasures {
+    // Depth gap, shoulder depth, ear slope and shoulder slope
+    front: Option<(f32, f32, f32, f32)>,
+    side_gap: Option<f32>,
+    visibility: f32,
+}
+
+/*
+Latest frame of every connected source, fused into a single set of measures for classification.
+Cameras don't share their coordinates: each frame is measured in the frame of its own camera,
+according to the view of its source, and only the measures are combined.
+*/
+#[derive(Default)]
+pub struct Fusion {
+    frames: BTreeMap<String, SourceFrame>,
+}
+
+impl Fusion {
+    pub fn update(
+        &mut self,
+        source: &str,
+        weight: f32,
+        view: CameraView,
+        landmarks: PoseLandmarks,
+        now: Instant,
+    ) {
+        self.frames.insert(
+            source.to_string(),
+            SourceFrame {
+                landmarks,
+                weight,
+                view,
+                received_at: now,
+            },
+        );
+    }
+
+    pub fn remove(&mut self, source: &str) {
+        self.frames.remove(source);
+    }
+
+    pub fn has_fresh_frames(&self, now: Instant, max_age: Duration) -> bool {
+        self.frames
+            .values()
+            .any(|frame| now.duration_since(frame.received_at) < max_age)
+    }
+
+    /*
+    Fuses the frames received within `max_age`.
+    Every measure is averaged over the sources able to measure it, weighted by the visibility
+    of the landmarks times the source weight, so a camera that sees the user clearly outweighs
+    one that barely does. Measures no source can take, e.g. the slopes with side cameras only,
+    are those of the baseline: they never count as a bad posture.
+    */
+    pub fn fuse(
+        &self,
+        now: Instant,
+        max_age: Duration,
+        min_visibility: f32,
+        baseline: &PostureBaseline,
+    ) -> FusedFrame {
+        let fresh: Vec<&SourceFrame> = self
+            .frames
+            .values()
+            .filter(|frame| now.duration_since(frame.received_at) < max_age)
+            .collect();
+
+        let landmarks = fresh
+            .iter()
+            .max_by(|a, b| {
+                (a.view == CameraView::Front, a.weight)
+                    .partial_cmp(&(b.view == CameraView::Front, b.weight))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .map(|frame| frame.landmarks.clone())
+            .unwrap_or_default();
+
+        let results: Vec<(Result<ViewMeasures, Posture>, f32)> = fresh
+            .iter()
+            .map(|frame| {
+                (
+                    measure(&frame.landmarks, frame.view, min_visibility),
+                    frame.weight,
+                )
+            })
+            .collect();
+
+        FusedFrame {
+            landmarks,
+            measures: combine(results, baseline),
+        }
+    }
+}
+
+// Measures a frame from a single camera, see `Fusion::fuse` for the measures it can't take
+pub fn measure_frame(
+    landmarks: &PoseLandmarks,
+    view: CameraView,
+    min_visibility: f32,
+    baseline: &PostureBaseline,
+) -> Measured {
+    combine(
+        vec![(measure(landmarks, view, min_visibility), 1.0)],
+        baseline,
+    )
+}
+
+fn measure(
+    landmarks: &PoseLandmarks,
+    view: CameraView,
+    min_visibility: f32,
+) -> Result<ViewMeasures, Posture> {
+    // Missing landmarks count as not visible
+    let visible = |landmark: Landmark| {
+        landmarks
+            .get(landmark)
+            .filter(|p| p.visibility >= min_visibility)
+    };
+    let average_visibility = |points: &[&Point3D]| {
+        points.iter().map(|p| p.visibility).sum::<f32>() / points.len() as f32
+    };
+
+    // A side camera only sees the ear and the shoulder facing it
+    let (ear, shoulder) = match view {
+        CameraView::Front => {
+            let (Some(left_shoulder), Some(right_shoulder)) = (
+                visible(Landmark::LeftShoulder),
+                visible(Landmark::RightShoulder),
+            ) else {
+                return Err(Posture::ShouldersNotVisible);
+            };
+            let (Some(left_ear), Some(right_ear)) =
+                (visible(Landmark::LeftEar), visible(Landmark::RightEar))
+            else {
+                return Err(Posture::HeadNotVisible);
+            };
+
+            let measures =
+                PostureMeasures::from_points(left_ear, right_ear, left_shoulder, right_shoulder);
+            return Ok(ViewMeasures {
+                front: Some((
+                    measures.depth_gap,
+                    measures.shoulder_depth,
+                    measures.ear_slope,
+                    measures.shoulder_slope,
+                )),
+                side_gap: None,
+                visibility: average_visibility(&[
+                    left_ear,
+                    right_ear,
+                    left_shoulder,
+                    right_shoulder,
+                ]),
+            });
+        }
+        CameraView::Left => (Landmark::LeftEar, Landmark::LeftShoulder),
+        CameraView::Right => (Landmark::RightEar, Landmark::RightShoulder),
+    };
+
+    let Some(shoulder) = visible(shoulder) else {
+        return Err(Posture::ShouldersNotVisible);
+    };
+    let Some(ear) = visible(ear) else {
+        return Err(Posture::HeadNotVisible);
+    };
+
+    // The user faces the left of the image of a camera on their left, the right of the other one
+    let side_gap = match view {
+        CameraView::Left => shoulder.x - ear.x,
+        _ => ear.x - shoulder.x,
+    };
+    Ok(ViewMeasures {
+        front: None,
+        side_gap: Some(side_gap),
+        visibility: average_visibility(&[ear, shoulder]),
+    })
+}
+
+fn combine(
+    results: Vec<(Result<ViewMeasures, Posture>, f32)>,
+    baseline: &PostureBaseline,
+) -> Measured {
+    let mut measured = Vec::new();
+    // The shoulders seen by any camera make the head the missing part
+    let mut issue = Posture::ShouldersNotVisible;
+    for (result, weight) in results {
+        match result {
+            Ok(measures) => {
+                let trust = (measures.visibility * weight).max(f32::EPSILON);
+                measured.push((measures, trust));
+            }
+            Err(Posture::HeadNotVisible) => issue = Posture::HeadNotVisible,
+            Err(_) => {}
+        }
+    }
+    if measured.is_empty() {
+        return Err(issue);
+    }
+
+    let average = |values: Vec<(f32, f32)>| {
+        let total: f32 = values.iter().map(|(_, trust)| trust).sum();
+        (!values.is_empty()).then(|| {
+            values
+                .iter()
+                .map(|(value, trust)| value * trust)
+                .sum::<f32>()
+                / total
+        })
+    };
+    let front = |get: fn(&(f32, f32, f32, f32)) -> f32| {
+        average(
+            measured
+                .iter()
+                .filter_map(|(measures, trust)| Some((get(measures.front.as_ref()?), *trust)))
+                .collect(),
+        )
+    };
+
+    Ok(PostureMeasures {
+        depth_gap: front(|front| front.0).unwrap_or(baseline.depth_gap),
+        shoulder_depth: front(|front| front.1).unwrap_or(baseline.shoulder_depth),
+        ear_slope: front(|front| front.2).unwrap_or(baseline.ear_slope),
+        shoulder_slope: front(|front| front.3).unwrap_or(baseline.shoulder_slope),
+        side_gap: average(
+            measured
+                .iter()
+                .filter_map(|(measures, trust)| Some((measures.side_gap?, *trust)))
+                .collect(),
+        )
+        .unwrap_or(baseline.side_gap),
+    })
+}
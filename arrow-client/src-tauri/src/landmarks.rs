@@ -0,0 +1,148 @@
+use crate::events::Point3D;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// The 33 landmarks of the MediaPipe pose skeleton, in MediaPipe index order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Landmark {
+    Nose,
+    LeftEyeInner,
+    LeftEye,
+    LeftEyeOuter,
+    RightEyeInner,
+    RightEye,
+    RightEyeOuter,
+    LeftEar,
+    RightEar,
+    MouthLeft,
+    MouthRight,
+    LeftShoulder,
+    RightShoulder,
+    LeftElbow,
+    RightElbow,
+    LeftWrist,
+    RightWrist,
+    LeftPinky,
+    RightPinky,
+    LeftIndex,
+    RightIndex,
+    LeftThumb,
+    RightThumb,
+    LeftHip,
+    RightHip,
+    LeftKnee,
+    RightKnee,
+    LeftAnkle,
+    RightAnkle,
+    LeftHeel,
+    RightHeel,
+    LeftFootIndex,
+    RightFootIndex,
+}
+
+impl Landmark {
+    pub const ALL: [Landmark; 33] = [
+        Landmark::Nose,
+        Landmark::LeftEyeInner,
+        Landmark::LeftEye,
+        Landmark::LeftEyeOuter,
+        Landmark::RightEyeInner,
+        Landmark::RightEye,
+        Landmark::RightEyeOuter,
+        Landmark::LeftEar,
+        Landmark::RightEar,
+        Landmark::MouthLeft,
+        Landmark::MouthRight,
+        Landmark::LeftShoulder,
+        Landmark::RightShoulder,
+        Landmark::LeftElbow,
+        Landmark::RightElbow,
+        Landmark::LeftWrist,
+        Landmark::RightWrist,
+        Landmark::LeftPinky,
+        Landmark::RightPinky,
+        Landmark::LeftIndex,
+        Landmark::RightIndex,
+        Landmark::LeftThumb,
+        Landmark::RightThumb,
+        Landmark::LeftHip,
+        Landmark::RightHip,
+        Landmark::LeftKnee,
+        Landmark::RightKnee,
+        Landmark::LeftAnkle,
+        Landmark::RightAnkle,
+        Landmark::LeftHeel,
+        Landmark::RightHeel,
+        Landmark::LeftFootIndex,
+        Landmark::RightFootIndex,
+    ];
+
+    pub fn get_name(&self) -> &'static str {
+        match &self {
+            Landmark::Nose => "nose",
+            Landmark::LeftEyeInner => "left_eye_inner",
+            Landmark::LeftEye => "left_eye",
+            Landmark::LeftEyeOuter => "left_eye_outer",
+            Landmark::RightEyeInner => "right_eye_inner",
+            Landmark::RightEye => "right_eye",
+            Landmark::RightEyeOuter => "right_eye_outer",
+            Landmark::LeftEar => "left_ear",
+            Landmark::RightEar => "right_ear",
+            Landmark::MouthLeft => "mouth_left",
+            Landmark::MouthRight => "mouth_right",
+            Landmark::LeftShoulder => "left_shoulder",
+            Landmark::RightShoulder => "right_shoulder",
+            Landmark::LeftElbow => "left_elbow",
+            Landmark::RightElbow => "right_elbow",
+            Landmark::LeftWrist => "left_wrist",
+            Landmark::RightWrist => "right_wrist",
+            Landmark::LeftPinky => "left_pinky",
+            Landmark::RightPinky => "right_pinky",
+            Landmark::LeftIndex => "left_index",
+            Landmark::RightIndex => "right_index",
+            Landmark::LeftThumb => "left_thumb",
+            Landmark::RightThumb => "right_thumb",
+            Landmark::LeftHip => "left_hip",
+            Landmark::RightHip => "right_hip",
+            Landmark::LeftKnee => "left_knee",
+            Landmark::RightKnee => "right_knee",
+            Landmark::LeftAnkle => "left_ankle",
+            Landmark::RightAnkle => "right_ankle",
+            Landmark::LeftHeel => "left_heel",
+            Landmark::RightHeel => "right_heel",
+            Landmark::LeftFootIndex => "left_foot_index",
+            Landmark::RightFootIndex => "right_foot_index",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Landmark> {
+        Landmark::ALL
+            .iter()
+            .find(|landmark| landmark.get_name() == name)
+            .copied()
+    }
+}
+
+/*
+All the landmarks received for a single frame, keyed by name.
+Every landmark is optional: legacy servers only send ears and shoulders,
+and MediaPipe may not report landmarks that are out of frame.
+*/
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PoseLandmarks(BTreeMap<Landmark, Point3D>);
+
+impl PoseLandmarks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, landmark: Landmark) -> Option<&Point3D> {
+        self.0.get(&landmark)
+    }
+
+    pub fn insert(&mut self, landmark: Landmark, point: Point3D) {
+        self.0.insert(landmark, point);
+    }
+}
@@ -1,24 +1,59 @@
+mod calibration;
+mod capture;
+mod classifier;
 mod db_manager;
+mod demo;
+mod discovery;
 mod events;
+mod fusion;
+mod landmarks;
 mod notification_service;
+mod pipeline;
 mod postures;
+mod protocol;
+mod reconnect;
+mod server_process;
+mod server_source;
+mod settings;
+mod smoothing;
+mod source;
+mod stage_queue;
+mod stream_stats;
 mod tcp_client;
+mod tls;
+mod transport;
 
 #[cfg(test)]
 mod tests;
 
+use calibration::{PostureBaseline, DEFAULT_CALIBRATION, MAX_CALIBRATION, MIN_CALIBRATION};
+use classifier::load_classifier;
 use db_manager::{DbManager, PostureLog, WeeklyStats};
-use events::ConnectionStatus;
+use discovery::ServerDiscovery;
+use events::{
+    ConnectionStatus, DiscoveredServer, MonitoringState, PostureIssue, QueueStats,
+    ServerProcessStatus, StreamDiagnostics, StreamStats,
+};
 use postures::Posture;
-use std::{net::TcpListener, process::Command, sync::Arc};
-use tauri::{AppHandle, State};
+use protocol::{ControlCommand, ServerInfo};
+use server_process::ServerSupervisor;
+use settings::{
+    ClassifierSettings, DemoSettings, DiscoverySettings, PostureThresholds, ReconnectPolicy,
+    ServerAddress, ServerProcessSettings, Settings, SmoothingSettings, SourceConfig,
+    StreamSettings, PRIMARY_SOURCE,
+};
+use std::{net::TcpListener, sync::Arc, time::Duration};
+use tauri::{AppHandle, Manager, RunEvent, State};
 use tcp_client::TcpClient;
 use tokio::sync::Mutex;
 
 pub struct AppState {
     pub db_manager: Arc<Mutex<Option<DbManager>>>,
     pub tcp_client: Arc<Mutex<Option<TcpClient>>>,
+    pub server_supervisor: Arc<Mutex<Option<ServerSupervisor>>>,
+    pub discovery: Arc<Mutex<Option<ServerDiscovery>>>,
     pub current_posture: Arc<Mutex<Posture>>,
+    pub settings: Arc<Mutex<Settings>>,
 }
 
 impl AppState {
@@ -26,11 +61,31 @@ impl AppState {
         Self {
             db_manager: Arc::new(Mutex::new(None)),
             tcp_client: Arc::new(Mutex::new(None)),
+            server_supervisor: Arc::new(Mutex::new(None)),
+            discovery: Arc::new(Mutex::new(None)),
             current_posture: Arc::new(Mutex::new(Posture::Unknown)),
+            settings: Arc::new(Mutex::new(Settings::load())),
+        }
+    }
+
+    // Kills the posture server started by the app, if any
+    pub async fn stop_server(&self) {
+        if let Some(supervisor) = self.server_supervisor.lock().await.as_ref() {
+            supervisor.stop().await;
         }
     }
 
     pub async fn cleanup(&self, current_posture: &str) {
+        if let Some(discovery) = self.discovery.lock().await.as_ref() {
+            discovery.stop().await;
+        }
+
+        // Disconnect the sources before the session ends
+        if let Some(tcp_client) = self.tcp_client.lock().await.as_ref() {
+            tcp_client.stop().await;
+        }
+        self.stop_server().await;
+
         // Log session end
         if let Some(db_manager) = self.db_manager.lock().await.as_ref() {
             let _ = db_manager.log_session_end(current_posture);
@@ -43,11 +98,12 @@ async fn initialize_app(
     app_handle: AppHandle,
     state: State<'_, AppState>,
 ) -> Result<String, String> {
-    println!("Starting Python server on port 9876");
-    let _python_server = Command::new("python")
-        .arg("/home/Thibault/dev/arrow/server/main.py")
-        .spawn();
-    println!("Started Python server on port 9876");
+    // The frontend may initialize again when it reloads, the client keeps running.
+    // Held until the client is stored, so concurrent calls wait instead of initializing twice
+    let mut tcp_lock = state.tcp_client.lock().await;
+    if tcp_lock.is_some() {
+        return Ok("Application already initialized".to_string());
+    }
 
     let db_manager = match DbManager::new() {
         Ok(manager) => {
@@ -66,17 +122,53 @@ async fn initialize_app(
         *db_lock = Some(db_manager);
     }
 
-    let tcp_client = TcpClient::new(app_handle.clone(), state.db_manager.clone());
+    // Only once the database is open, a failed initialization leaves no server running
+    let server_process = state.settings.lock().await.server_process.clone();
+    let supervisor = ServerSupervisor::new(app_handle.clone());
+    supervisor.start(server_process).await;
+    {
+        let mut supervisor_lock = state.server_supervisor.lock().await;
+        *supervisor_lock = Some(supervisor);
+    }
+
+    let server_address = state.settings.lock().await.server.clone();
+    let tcp_client = TcpClient::new(
+        app_handle.clone(),
+        state.db_manager.clone(),
+        server_address,
+        state.settings.clone(),
+    );
 
     if let Err(e) = tcp_client.initialize_notifications().await {
         eprintln!("Failed to initialize notifications: {}", e);
     }
 
-    tcp_client.start().await;
+    // A missing or broken model shouldn't stop the monitoring, the rules are used instead
+    let classifier_settings = state.settings.lock().await.classifier.clone();
+    match load_classifier(&classifier_settings) {
+        Ok(classifier) => tcp_client.set_classifier(classifier).await,
+        Err(e) => eprintln!("{}, classifying with the rules", e),
+    }
+
+    tcp_client.start().await?;
 
+    *tcp_lock = Some(tcp_client);
+    // Discovery switches the server of the stored client
+    drop(tcp_lock);
+
+    // Discovery is optional, the configured address is used as is if it can't listen
+    let discovery_settings = state.settings.lock().await.discovery.clone();
+    let discovery = ServerDiscovery::new(
+        app_handle.clone(),
+        state.settings.clone(),
+        state.tcp_client.clone(),
+    );
+    if let Err(e) = discovery.start(discovery_settings).await {
+        eprintln!("{}", e);
+    }
     {
-        let mut tcp_lock = state.tcp_client.lock().await;
-        *tcp_lock = Some(tcp_client);
+        let mut discovery_lock = state.discovery.lock().await;
+        *discovery_lock = Some(discovery);
     }
 
     Ok("Application initialized successfully".to_string())
@@ -95,27 +187,539 @@ async fn get_session_logs(state: State<'_, AppState>) -> Result<Option<Vec<Postu
     }
 }
 
+// Issues that led to the latest logged posture change, the most important first
+#[tauri::command]
+async fn get_last_change_issues(state: State<'_, AppState>) -> Result<Vec<PostureIssue>, String> {
+    let db_lock = state.db_manager.lock().await;
+    if let Some(db_manager) = db_lock.as_ref() {
+        match db_manager.get_last_change_issues() {
+            Ok(issues) => Ok(issues
+                .into_iter()
+                .map(|(posture, severity)| PostureIssue {
+                    posture: Posture::from(posture),
+                    severity,
+                })
+                .collect()),
+            Err(e) => Err(format!("Failed to get posture issues: {}", e)),
+        }
+    } else {
+        Err("Database not initialized".to_string())
+    }
+}
+
 #[tauri::command]
 async fn get_connection_status(state: State<'_, AppState>) -> Result<ConnectionStatus, String> {
     let tcp_lock = state.tcp_client.lock().await;
     if let Some(tcp_client) = tcp_lock.as_ref() {
-        let connected = tcp_client.is_connected().await;
-        Ok(ConnectionStatus {
-            connected,
-            message: if connected {
-                "Connected to posture server".to_string()
-            } else {
-                "Not connected to server".to_string()
-            },
-        })
+        Ok(tcp_client.get_connection_status().await)
     } else {
         Ok(ConnectionStatus {
             connected: false,
             message: "TCP client not initialized".to_string(),
+            server_address: state.settings.lock().await.server.to_string(),
+            ..Default::default()
         })
     }
 }
 
+#[tauri::command]
+async fn get_server_info(state: State<'_, AppState>) -> Result<Option<ServerInfo>, String> {
+    let tcp_lock = state.tcp_client.lock().await;
+    if let Some(tcp_client) = tcp_lock.as_ref() {
+        Ok(tcp_client.get_server_info().await)
+    } else {
+        Err("TCP client not initialized".to_string())
+    }
+}
+
+#[tauri::command]
+async fn get_stream_diagnostics(
+    state: State<'_, AppState>,
+) -> Result<Vec<StreamDiagnostics>, String> {
+    let tcp_lock = state.tcp_client.lock().await;
+    if let Some(tcp_client) = tcp_lock.as_ref() {
+        Ok(tcp_client.get_diagnostics().await)
+    } else {
+        Err("TCP client not initialized".to_string())
+    }
+}
+
+#[tauri::command]
+async fn get_stream_stats(state: State<'_, AppState>) -> Result<Vec<StreamStats>, String> {
+    let tcp_lock = state.tcp_client.lock().await;
+    if let Some(tcp_client) = tcp_lock.as_ref() {
+        Ok(tcp_client.get_stream_stats().await)
+    } else {
+        Err("TCP client not initialized".to_string())
+    }
+}
+
+#[tauri::command]
+async fn get_queue_stats(state: State<'_, AppState>) -> Result<Vec<QueueStats>, String> {
+    let tcp_lock = state.tcp_client.lock().await;
+    if let Some(tcp_client) = tcp_lock.as_ref() {
+        Ok(tcp_client.get_queue_stats())
+    } else {
+        Err("TCP client not initialized".to_string())
+    }
+}
+
+#[tauri::command]
+async fn get_server_address(state: State<'_, AppState>) -> Result<ServerAddress, String> {
+    Ok(state.settings.lock().await.server.clone())
+}
+
+#[tauri::command]
+async fn set_server_address(
+    address: ServerAddress,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    address.validate()?;
+
+    {
+        let mut settings = state.settings.lock().await;
+        settings.server = address.clone();
+        if let Err(e) = settings.save() {
+            return Err(format!("Failed to save settings: {}", e));
+        }
+    }
+
+    // Make a running client drop its connection and reconnect to the new address
+    if let Some(tcp_client) = state.tcp_client.lock().await.as_ref() {
+        tcp_client.set_server_address(address);
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn list_discovered_servers(
+    state: State<'_, AppState>,
+) -> Result<Vec<DiscoveredServer>, String> {
+    let discovery_lock = state.discovery.lock().await;
+    if let Some(discovery) = discovery_lock.as_ref() {
+        Ok(discovery.get_servers().await)
+    } else {
+        Err("Discovery not initialized".to_string())
+    }
+}
+
+#[tauri::command]
+async fn get_discovery_settings(state: State<'_, AppState>) -> Result<DiscoverySettings, String> {
+    Ok(state.settings.lock().await.discovery.clone())
+}
+
+#[tauri::command]
+async fn set_discovery_settings(
+    discovery_settings: DiscoverySettings,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    discovery_settings.validate()?;
+
+    {
+        let mut settings = state.settings.lock().await;
+        settings.discovery = discovery_settings.clone();
+        if let Err(e) = settings.save() {
+            return Err(format!("Failed to save settings: {}", e));
+        }
+    }
+
+    // Listens again with the new settings
+    if let Some(discovery) = state.discovery.lock().await.as_ref() {
+        discovery.start(discovery_settings).await?;
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_sources(state: State<'_, AppState>) -> Result<Vec<SourceConfig>, String> {
+    Ok(state.settings.lock().await.sources.clone())
+}
+
+#[tauri::command]
+async fn set_sources(sources: Vec<SourceConfig>, state: State<'_, AppState>) -> Result<(), String> {
+    SourceConfig::validate_all(&sources)?;
+
+    {
+        let mut settings = state.settings.lock().await;
+        settings.sources = sources.clone();
+        if let Err(e) = settings.save() {
+            return Err(format!("Failed to save settings: {}", e));
+        }
+    }
+
+    // Reconnect the additional sources, the primary connection is left untouched
+    if let Some(tcp_client) = state.tcp_client.lock().await.as_ref() {
+        tcp_client.set_sources(sources).await;
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_demo_settings(state: State<'_, AppState>) -> Result<DemoSettings, String> {
+    Ok(state.settings.lock().await.demo.clone())
+}
+
+#[tauri::command]
+async fn set_demo_settings(
+    demo_settings: DemoSettings,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    demo_settings.validate()?;
+
+    {
+        let mut settings = state.settings.lock().await;
+        settings.demo = demo_settings.clone();
+        if let Err(e) = settings.save() {
+            return Err(format!("Failed to save settings: {}", e));
+        }
+    }
+
+    // Starts, restarts or stops the demo source right away
+    if let Some(tcp_client) = state.tcp_client.lock().await.as_ref() {
+        tcp_client.set_demo(demo_settings).await;
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn start_recording(state: State<'_, AppState>) -> Result<String, String> {
+    let tcp_lock = state.tcp_client.lock().await;
+    if let Some(tcp_client) = tcp_lock.as_ref() {
+        let path = tcp_client.start_recording().await?;
+        Ok(path.display().to_string())
+    } else {
+        Err("TCP client not initialized".to_string())
+    }
+}
+
+#[tauri::command]
+async fn stop_recording(state: State<'_, AppState>) -> Result<Option<String>, String> {
+    let tcp_lock = state.tcp_client.lock().await;
+    if let Some(tcp_client) = tcp_lock.as_ref() {
+        let path = tcp_client.stop_recording().await;
+        Ok(path.map(|path| path.display().to_string()))
+    } else {
+        Err("TCP client not initialized".to_string())
+    }
+}
+
+#[tauri::command]
+async fn get_recording_path(state: State<'_, AppState>) -> Result<Option<String>, String> {
+    let tcp_lock = state.tcp_client.lock().await;
+    if let Some(tcp_client) = tcp_lock.as_ref() {
+        let path = tcp_client.get_recording_path().await;
+        Ok(path.map(|path| path.display().to_string()))
+    } else {
+        Err("TCP client not initialized".to_string())
+    }
+}
+
+#[tauri::command]
+async fn start_monitoring(state: State<'_, AppState>) -> Result<(), String> {
+    let tcp_lock = state.tcp_client.lock().await;
+    if let Some(tcp_client) = tcp_lock.as_ref() {
+        tcp_client.start().await
+    } else {
+        Err("TCP client not initialized".to_string())
+    }
+}
+
+#[tauri::command]
+async fn stop_monitoring(state: State<'_, AppState>) -> Result<(), String> {
+    let tcp_lock = state.tcp_client.lock().await;
+    if let Some(tcp_client) = tcp_lock.as_ref() {
+        tcp_client.stop().await;
+        Ok(())
+    } else {
+        Err("TCP client not initialized".to_string())
+    }
+}
+
+#[tauri::command]
+async fn pause_monitoring(state: State<'_, AppState>) -> Result<(), String> {
+    let tcp_lock = state.tcp_client.lock().await;
+    if let Some(tcp_client) = tcp_lock.as_ref() {
+        tcp_client.pause().await
+    } else {
+        Err("TCP client not initialized".to_string())
+    }
+}
+
+#[tauri::command]
+async fn resume_monitoring(state: State<'_, AppState>) -> Result<(), String> {
+    let tcp_lock = state.tcp_client.lock().await;
+    if let Some(tcp_client) = tcp_lock.as_ref() {
+        tcp_client.resume().await
+    } else {
+        Err("TCP client not initialized".to_string())
+    }
+}
+
+#[tauri::command]
+async fn get_monitoring_state(state: State<'_, AppState>) -> Result<MonitoringState, String> {
+    let tcp_lock = state.tcp_client.lock().await;
+    if let Some(tcp_client) = tcp_lock.as_ref() {
+        Ok(tcp_client.get_state().await)
+    } else {
+        Err("TCP client not initialized".to_string())
+    }
+}
+
+// Sends a command to the server of a source, the primary one by default
+async fn send_server_command(
+    state: &AppState,
+    source: Option<String>,
+    command: ControlCommand,
+) -> Result<String, String> {
+    let source = source.unwrap_or_else(|| PRIMARY_SOURCE.to_string());
+
+    // Don't keep the client locked while waiting for the acknowledgement
+    let reply = {
+        let tcp_lock = state.tcp_client.lock().await;
+        let Some(tcp_client) = tcp_lock.as_ref() else {
+            return Err("TCP client not initialized".to_string());
+        };
+        tcp_client.send_command(&source, command).await?
+    };
+
+    server_source::wait_for_ack(reply).await
+}
+
+#[tauri::command]
+async fn set_server_frame_rate(
+    frame_rate: f32,
+    source: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<String, String> {
+    if !(frame_rate > 0.0 && frame_rate <= 120.0) {
+        return Err("Frame rate must be between 0 and 120".to_string());
+    }
+
+    send_server_command(&state, source, ControlCommand::SetFrameRate { frame_rate }).await
+}
+
+#[tauri::command]
+async fn start_server_calibration(
+    source: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<String, String> {
+    send_server_command(&state, source, ControlCommand::StartCalibration).await
+}
+
+#[tauri::command]
+async fn shutdown_server(
+    source: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<String, String> {
+    send_server_command(&state, source, ControlCommand::Shutdown).await
+}
+
+#[tauri::command]
+async fn get_reconnect_policy(state: State<'_, AppState>) -> Result<ReconnectPolicy, String> {
+    Ok(state.settings.lock().await.reconnect.clone())
+}
+
+#[tauri::command]
+async fn set_reconnect_policy(
+    policy: ReconnectPolicy,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    policy.validate()?;
+
+    // Picked up by the client on its next reconnection attempt
+    let mut settings = state.settings.lock().await;
+    settings.reconnect = policy;
+    if let Err(e) = settings.save() {
+        return Err(format!("Failed to save settings: {}", e));
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_stream_settings(state: State<'_, AppState>) -> Result<StreamSettings, String> {
+    Ok(state.settings.lock().await.stream.clone())
+}
+
+#[tauri::command]
+async fn set_stream_settings(
+    stream_settings: StreamSettings,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    stream_settings.validate()?;
+
+    // Picked up by the client while waiting for the next frame
+    let mut settings = state.settings.lock().await;
+    settings.stream = stream_settings;
+    if let Err(e) = settings.save() {
+        return Err(format!("Failed to save settings: {}", e));
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_posture_thresholds(state: State<'_, AppState>) -> Result<PostureThresholds, String> {
+    Ok(state.settings.lock().await.thresholds.clone())
+}
+
+#[tauri::command]
+async fn set_posture_thresholds(
+    thresholds: PostureThresholds,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    thresholds.validate()?;
+
+    // Picked up by the pipeline when it classifies the next frame
+    let mut settings = state.settings.lock().await;
+    settings.thresholds = thresholds;
+    if let Err(e) = settings.save() {
+        return Err(format!("Failed to save settings: {}", e));
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_classifier_settings(state: State<'_, AppState>) -> Result<ClassifierSettings, String> {
+    Ok(state.settings.lock().await.classifier.clone())
+}
+
+#[tauri::command]
+async fn set_classifier_settings(
+    classifier: ClassifierSettings,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    // Loaded first, so a broken model is reported and the current classifier kept
+    let loaded = load_classifier(&classifier)?;
+
+    {
+        let mut settings = state.settings.lock().await;
+        settings.classifier = classifier;
+        if let Err(e) = settings.save() {
+            return Err(format!("Failed to save settings: {}", e));
+        }
+    }
+
+    if let Some(tcp_client) = state.tcp_client.lock().await.as_ref() {
+        tcp_client.set_classifier(loaded).await;
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_smoothing_settings(state: State<'_, AppState>) -> Result<SmoothingSettings, String> {
+    Ok(state.settings.lock().await.smoothing.clone())
+}
+
+#[tauri::command]
+async fn set_smoothing_settings(
+    smoothing: SmoothingSettings,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    smoothing.validate()?;
+
+    // Picked up by the pipeline when it classifies the next frame
+    let mut settings = state.settings.lock().await;
+    settings.smoothing = smoothing;
+    if let Err(e) = settings.save() {
+        return Err(format!("Failed to save settings: {}", e));
+    }
+
+    Ok(())
+}
+
+// Asks the user to sit straight for `duration_secs`, 5 seconds by default
+#[tauri::command]
+async fn start_posture_calibration(
+    duration_secs: Option<u64>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let duration = duration_secs
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_CALIBRATION);
+    if !(MIN_CALIBRATION..=MAX_CALIBRATION).contains(&duration) {
+        return Err(format!(
+            "Calibration must last between {} and {} seconds",
+            MIN_CALIBRATION.as_secs(),
+            MAX_CALIBRATION.as_secs()
+        ));
+    }
+
+    let tcp_lock = state.tcp_client.lock().await;
+    if let Some(tcp_client) = tcp_lock.as_ref() {
+        tcp_client.start_calibration(duration).await
+    } else {
+        Err("TCP client not initialized".to_string())
+    }
+}
+
+#[tauri::command]
+async fn get_posture_baseline(
+    state: State<'_, AppState>,
+) -> Result<Option<PostureBaseline>, String> {
+    Ok(state.settings.lock().await.baseline.clone())
+}
+
+// Back to the absolute thresholds from the next frame
+#[tauri::command]
+async fn reset_posture_baseline(state: State<'_, AppState>) -> Result<(), String> {
+    let mut settings = state.settings.lock().await;
+    settings.baseline = None;
+    if let Err(e) = settings.save() {
+        return Err(format!("Failed to save settings: {}", e));
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_server_process_settings(
+    state: State<'_, AppState>,
+) -> Result<ServerProcessSettings, String> {
+    Ok(state.settings.lock().await.server_process.clone())
+}
+
+#[tauri::command]
+async fn set_server_process_settings(
+    server_process: ServerProcessSettings,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    server_process.validate()?;
+
+    {
+        let mut settings = state.settings.lock().await;
+        settings.server_process = server_process.clone();
+        if let Err(e) = settings.save() {
+            return Err(format!("Failed to save settings: {}", e));
+        }
+    }
+
+    // Restarts the server with the new settings
+    if let Some(supervisor) = state.server_supervisor.lock().await.as_ref() {
+        supervisor.start(server_process).await;
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_server_process_status(
+    state: State<'_, AppState>,
+) -> Result<ServerProcessStatus, String> {
+    let supervisor_lock = state.server_supervisor.lock().await;
+    if let Some(supervisor) = supervisor_lock.as_ref() {
+        Ok(supervisor.get_status().await)
+    } else {
+        Err("Server supervisor not initialized".to_string())
+    }
+}
+
 #[tauri::command]
 async fn log_posture_change(
     current_posture: String,
@@ -124,7 +728,7 @@ async fn log_posture_change(
 ) -> Result<(), String> {
     let db_lock = state.db_manager.lock().await;
     if let Some(db_manager) = db_lock.as_ref() {
-        match db_manager.log_posture_change(&current_posture, &previous_posture) {
+        match db_manager.log_posture_change(&current_posture, &previous_posture, &[]) {
             Ok(()) => Ok(()),
             Err(e) => Err(format!("Failed to log posture change: {}", e)),
         }
@@ -168,11 +772,59 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             initialize_app,
             get_session_logs,
+            get_last_change_issues,
             get_connection_status,
+            get_server_info,
+            get_stream_diagnostics,
+            get_stream_stats,
+            get_queue_stats,
+            get_server_address,
+            set_server_address,
+            list_discovered_servers,
+            get_discovery_settings,
+            set_discovery_settings,
+            get_sources,
+            set_sources,
+            get_demo_settings,
+            set_demo_settings,
+            start_recording,
+            stop_recording,
+            get_recording_path,
+            start_monitoring,
+            stop_monitoring,
+            pause_monitoring,
+            resume_monitoring,
+            get_monitoring_state,
+            set_server_frame_rate,
+            start_server_calibration,
+            shutdown_server,
+            get_reconnect_policy,
+            set_reconnect_policy,
+            get_stream_settings,
+            set_stream_settings,
+            get_posture_thresholds,
+            set_posture_thresholds,
+            get_classifier_settings,
+            set_classifier_settings,
+            get_smoothing_settings,
+            set_smoothing_settings,
+            start_posture_calibration,
+            get_posture_baseline,
+            reset_posture_baseline,
+            get_server_process_settings,
+            set_server_process_settings,
+            get_server_process_status,
             log_posture_change,
             get_weekly_stats,
             cleanup_app
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app_handle, event| {
+            // The frontend may not get to call cleanup_app, the server must not outlive the app
+            if let RunEvent::Exit = event {
+                let state = app_handle.state::<AppState>();
+                tauri::async_runtime::block_on(state.stop_server());
+            }
+        });
 }
@@ -0,0 +1,729 @@
+use crate::calibration::Calibration;
+use crate::classifier::{build_update, get_primary_posture, PostureClassifier, RuleClassifier};
+use crate::db_manager::DbManager;
+use crate::events::{
+    CalibrationResult, ConnectionStatus, MonitoringState, MonitoringStateUpdate, NotificationEvent,
+    PostureIssue, PostureUpdate, QueueStats, SessionLogsUpdate, SourceStatus, StreamDiagnostics,
+    StreamStale, StreamStats,
+};
+use crate::fusion::{FusedFrame, Fusion};
+use crate::landmarks::PoseLandmarks;
+use crate::notification_service::NotificationService;
+use crate::postures::Posture;
+use crate::protocol::{FrameMetadata, ServerInfo};
+use crate::settings::{CameraView, Settings};
+use crate::smoothing::PostureSmoother;
+use crate::source::{FrameSink, PostureSource};
+use crate::stage_queue::StageQueue;
+use crate::stream_stats::StreamStatsTracker;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter};
+use tokio::sync::{mpsc, Mutex};
+use tokio::task::JoinHandle;
+use tokio::time::{interval, interval_at, Duration, Instant};
+
+// How often the frame and queue statistics are emitted
+const STATS_INTERVAL: Duration = Duration::from_secs(5);
+// How often the calibration progress is emitted
+const CALIBRATION_PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+const FRAME_QUEUE_SIZE: usize = 32;
+const PERSISTENCE_QUEUE_SIZE: usize = 64;
+const NOTIFICATION_QUEUE_SIZE: usize = 8;
+
+/*
+Frames waiting for classification.
+The landmarks are already in the fusion when the event is queued, and every classification
+uses the latest frames of all the sources: a dropped frame event is coalesced into the next one.
+*/
+enum FrameEvent {
+    Frame {
+        source: String,
+        metadata: FrameMetadata,
+    },
+    // None of the sources sent a recent frame
+    Stale {
+        silence: Duration,
+    },
+    // Monitoring was paused or stopped
+    Paused,
+}
+
+/*
+Database writes, never dropped: a missing one is a posture change missing from the session logs.
+When the disk falls behind the classification waits, and the frame queue drops frames instead.
+*/
+enum PersistenceEvent {
+    PostureChanged {
+        current: String,
+        previous: String,
+        issues: Vec<PostureIssue>,
+    },
+    Stale {
+        previous: String,
+    },
+    Paused {
+        previous: String,
+    },
+}
+
+// Only the latest request matters, older ones still queued are skipped
+enum NotificationRequest {
+    PostureChanged(Posture),
+    Close,
+}
+
+struct StageReceivers {
+    frames: mpsc::Receiver<FrameEvent>,
+    persistence: mpsc::Receiver<PersistenceEvent>,
+    notifications: mpsc::Receiver<NotificationRequest>,
+}
+
+/*
+Consumes the frames of every source in separate stages joined by bounded queues:
+sources push frames into the fusion from their own task (ingestion), one task classifies the
+posture and emits the events shown by the frontend, one logs the changes to the database and
+one sends the notifications. A slow stage drops work from its queue instead of stalling the
+stages before it, except for the database writes, and the sources never wait on the database
+or the notifications.
+Cheap to clone, every source task holds a copy through its sink.
+*/
+#[derive(Clone)]
+pub struct Pipeline {
+    app_handle: AppHandle,
+    db_manager: Arc<Mutex<Option<DbManager>>>,
+    notification_service: Arc<NotificationService>,
+    current_posture: Arc<Mutex<Posture>>,
+    sources: Arc<Mutex<BTreeMap<String, SourceStatus>>>,
+    fusion: Arc<Mutex<Fusion>>,
+    stats: Arc<Mutex<BTreeMap<String, StreamStatsTracker>>>,
+    settings: Arc<Mutex<Settings>>,
+    // Frames are ignored while paused
+    paused: Arc<Mutex<bool>>,
+    classifier: Arc<Mutex<Arc<dyn PostureClassifier>>>,
+    smoother: Arc<Mutex<PostureSmoother>>,
+    // Collects the classified frames while the user calibrates their neutral posture
+    calibration: Arc<Mutex<Option<Calibration>>>,
+    frames: StageQueue<FrameEvent>,
+    persistence: StageQueue<PersistenceEvent>,
+    notifications: StageQueue<NotificationRequest>,
+    // Handed over to the stage tasks when the pipeline is started
+    receivers: Arc<Mutex<Option<StageReceivers>>>,
+}
+
+impl Pipeline {
+    pub fn new(
+        app_handle: AppHandle,
+        db_manager: Arc<Mutex<Option<DbManager>>>,
+        settings: Arc<Mutex<Settings>>,
+    ) -> Self {
+        let (frames, frame_receiver) = StageQueue::new("frames", FRAME_QUEUE_SIZE);
+        let (persistence, persistence_receiver) =
+            StageQueue::new("persistence", PERSISTENCE_QUEUE_SIZE);
+        let (notifications, notification_receiver) =
+            StageQueue::new("notifications", NOTIFICATION_QUEUE_SIZE);
+
+        Self {
+            app_handle,
+            db_manager,
+            notification_service: Arc::new(NotificationService::new()),
+            current_posture: Arc::new(Mutex::new(Posture::Unknown)),
+            sources: Arc::new(Mutex::new(BTreeMap::new())),
+            fusion: Arc::new(Mutex::new(Fusion::default())),
+            stats: Arc::new(Mutex::new(BTreeMap::new())),
+            settings,
+            paused: Arc::new(Mutex::new(false)),
+            classifier: Arc::new(Mutex::new(Arc::new(RuleClassifier))),
+            smoother: Arc::new(Mutex::new(PostureSmoother::default())),
+            calibration: Arc::new(Mutex::new(None)),
+            frames,
+            persistence,
+            notifications,
+            receivers: Arc::new(Mutex::new(Some(StageReceivers {
+                frames: frame_receiver,
+                persistence: persistence_receiver,
+                notifications: notification_receiver,
+            }))),
+        }
+    }
+
+    // Spawns the stage tasks and the statistics reporter, they run for as long as the app
+    pub async fn start(&self) {
+        let Some(receivers) = self.receivers.lock().await.take() else {
+            return;
+        };
+
+        tokio::spawn(self.clone().run_classification(receivers.frames));
+        tokio::spawn(self.clone().run_persistence(receivers.persistence));
+        tokio::spawn(self.clone().run_notifications(receivers.notifications));
+        self.spawn_stats_reporter();
+    }
+
+    pub async fn initialize_notifications(&self) -> Result<(), String> {
+        self.notification_service.initialize().await
+    }
+
+    pub fn spawn_source<S: PostureSource>(&self, source: S) -> JoinHandle<()> {
+        let sink = FrameSink::new(
+            source.get_name(),
+            source.get_weight(),
+            source.get_view(),
+            self.clone(),
+        );
+        tokio::spawn(source.run(sink))
+    }
+
+    // Forgets a source whose task was stopped
+    pub async fn remove_source(&self, source: &str) {
+        self.sources.lock().await.remove(source);
+        self.fusion.lock().await.remove(source);
+        self.stats.lock().await.remove(source);
+    }
+
+    // Emits the frame statistics of every source that sent frames, and the queue statistics
+    fn spawn_stats_reporter(&self) -> JoinHandle<()> {
+        let pipeline = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = interval_at(Instant::now() + STATS_INTERVAL, STATS_INTERVAL);
+            loop {
+                ticker.tick().await;
+                for stats in pipeline.get_stream_stats().await {
+                    let _ = pipeline.app_handle.emit("stream-stats", stats);
+                }
+                let _ = pipeline
+                    .app_handle
+                    .emit("queue-stats", pipeline.get_queue_stats());
+            }
+        })
+    }
+
+    pub fn report_monitoring_state(&self, state: MonitoringState) {
+        let _ = self
+            .app_handle
+            .emit("monitoring-state", MonitoringStateUpdate { state });
+    }
+
+    // Classifies the next frames, the current posture is kept until the new classifier changes it
+    pub async fn set_classifier(&self, classifier: Arc<dyn PostureClassifier>) {
+        *self.classifier.lock().await = classifier;
+    }
+
+    /*
+    While paused the frames are ignored and the posture stays Paused, which is logged
+    so the time isn't counted as good or bad posture. The pause goes through the frame queue
+    so the frames received before it are classified first.
+    */
+    pub async fn set_paused(&self, paused: bool) {
+        *self.paused.lock().await = paused;
+        if paused {
+            self.frames.send(FrameEvent::Paused).await;
+        }
+    }
+
+    /*
+    Measures the neutral posture of the user from the frames classified during `duration`
+    and saves it as their baseline. The progress is emitted as `calibration-progress` events
+    and the outcome as a `calibration-finished` event.
+    */
+    pub async fn start_calibration(&self, duration: Duration) -> Result<(), String> {
+        {
+            let mut calibration = self.calibration.lock().await;
+            if calibration.is_some() {
+                return Err("Calibration already in progress".to_string());
+            }
+            *calibration = Some(Calibration::new(duration, Instant::now()));
+        }
+
+        tokio::spawn(self.clone().run_calibration());
+        Ok(())
+    }
+
+    async fn run_calibration(self) {
+        let mut ticker = interval(CALIBRATION_PROGRESS_INTERVAL);
+        let calibration = loop {
+            ticker.tick().await;
+            let now = Instant::now();
+            let mut calibration = self.calibration.lock().await;
+            if let Some(current) = calibration.as_ref() {
+                let _ = self
+                    .app_handle
+                    .emit("calibration-progress", current.get_progress(now));
+                if !current.is_done(now) {
+                    continue;
+                }
+            }
+            break calibration.take();
+        };
+        let Some(calibration) = calibration else {
+            return;
+        };
+
+        let result = match calibration.finish(Self::get_unix_millis()) {
+            Ok(baseline) => {
+                let mut settings = self.settings.lock().await;
+                settings.baseline = Some(baseline.clone());
+                if let Err(e) = settings.save() {
+                    eprintln!("Failed to save settings: {}", e);
+                }
+
+                CalibrationResult {
+                    success: true,
+                    message: format!("Calibrated from {} frames", baseline.samples),
+                    baseline: Some(baseline),
+                }
+            }
+            Err(message) => CalibrationResult {
+                success: false,
+                message,
+                baseline: None,
+            },
+        };
+        let _ = self.app_handle.emit("calibration-finished", result);
+    }
+
+    pub fn get_queue_stats(&self) -> Vec<QueueStats> {
+        vec![
+            self.frames.get_stats(),
+            self.persistence.get_stats(),
+            self.notifications.get_stats(),
+        ]
+    }
+
+    pub async fn get_stream_stats(&self) -> Vec<StreamStats> {
+        let now = Instant::now();
+        self.stats
+            .lock()
+            .await
+            .iter()
+            .map(|(source, tracker)| tracker.get_stats(source, now))
+            .collect()
+    }
+
+    pub async fn get_sources(&self) -> Vec<SourceStatus> {
+        self.sources.lock().await.values().cloned().collect()
+    }
+
+    pub async fn get_server_info(&self, source: &str) -> Option<ServerInfo> {
+        self.sources.lock().await.get(source)?.server_info.clone()
+    }
+
+    /*
+    Records the status of one source and emits it along with the health of all the sources.
+    With several sources the message is prefixed with the name of the source it is about.
+    */
+    pub async fn report_status(&self, source: &str, mut status: ConnectionStatus) {
+        let mut sources = self.sources.lock().await;
+        let source_status = sources.entry(source.to_string()).or_default();
+        source_status.name = source.to_string();
+        source_status.server_address = status.server_address.clone();
+        source_status.connected = status.connected;
+        source_status.message = status.message.clone();
+        source_status.attempt = status.attempt;
+
+        if sources.len() > 1 {
+            status.message = format!("{}: {}", source, status.message);
+        }
+        status.connected = sources.values().any(|source| source.connected);
+        status.sources = sources.values().cloned().collect();
+        drop(sources);
+
+        let _ = self.app_handle.emit("connection-status", status);
+    }
+
+    pub fn report_diagnostics(&self, diagnostics: StreamDiagnostics) {
+        let _ = self.app_handle.emit("stream-diagnostics", diagnostics);
+    }
+
+    pub async fn set_server_info(&self, source: &str, info: Option<ServerInfo>) {
+        if let Some(info) = &info {
+            let _ = self.app_handle.emit("server-info", info.clone());
+        }
+        if let Some(status) = self.sources.lock().await.get_mut(source) {
+            status.server_info = info;
+        }
+    }
+
+    pub async fn source_disconnected(&self, source: &str) {
+        self.fusion.lock().await.remove(source);
+        if let Some(tracker) = self.stats.lock().await.get_mut(source) {
+            tracker.reset_sequence();
+        }
+        if let Some(status) = self.sources.lock().await.get_mut(source) {
+            status.server_info = None;
+            status.stale = false;
+        }
+    }
+
+    pub async fn push_frame(
+        &self,
+        source: &str,
+        weight: f32,
+        view: CameraView,
+        landmarks: PoseLandmarks,
+        metadata: FrameMetadata,
+    ) {
+        self.stats
+            .lock()
+            .await
+            .entry(source.to_string())
+            .or_default()
+            .record_frame(&metadata, Instant::now());
+
+        if *self.paused.lock().await {
+            return;
+        }
+
+        let recovered = {
+            let mut sources = self.sources.lock().await;
+            match sources.get_mut(source) {
+                Some(status) => {
+                    status.last_frame_at = Some(Self::get_unix_millis());
+                    std::mem::replace(&mut status.stale, false)
+                        .then(|| status.server_address.clone())
+                }
+                None => None,
+            }
+        };
+        if let Some(server_address) = recovered {
+            self.report_status(
+                source,
+                ConnectionStatus {
+                    connected: true,
+                    message: "Connected to posture server".to_string(),
+                    server_address,
+                    ..Default::default()
+                },
+            )
+            .await;
+        }
+
+        self.fusion
+            .lock()
+            .await
+            .update(source, weight, view, landmarks, Instant::now());
+        self.frames.push(FrameEvent::Frame {
+            source: source.to_string(),
+            metadata,
+        });
+    }
+
+    async fn run_classification(self, mut events: mpsc::Receiver<FrameEvent>) {
+        while let Some(event) = events.recv().await {
+            match event {
+                FrameEvent::Frame { source, metadata } => {
+                    self.classify_frame(&source, metadata).await
+                }
+                FrameEvent::Stale { silence } => self.mark_stream_stale(silence).await,
+                FrameEvent::Paused => self.mark_paused().await,
+            }
+        }
+    }
+
+    async fn classify_frame(&self, source: &str, metadata: FrameMetadata) {
+        // Queued right before the pause
+        if *self.paused.lock().await {
+            return;
+        }
+
+        // Classify what all the sources see together rather than this frame alone
+        let frame = {
+            let (frame_timeout, min_visibility, baseline) = {
+                let settings = self.settings.lock().await;
+                (
+                    Duration::from_millis(settings.stream.frame_timeout_ms),
+                    settings.thresholds.min_visibility,
+                    settings.baseline.clone().unwrap_or_default(),
+                )
+            };
+            self.fusion
+                .lock()
+                .await
+                .fuse(Instant::now(), frame_timeout, min_visibility, &baseline)
+        };
+
+        self.handle_frame(frame).await;
+
+        // Measured once the posture for the frame is out
+        if let Some(captured_at) = metadata.captured_at {
+            // Clamped, the clocks of the client and a remote server can drift apart
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs_f64() * 1000.0)
+                .unwrap_or(0.0);
+            let latency_ms = (now - captured_at).max(0.0);
+            if let Some(tracker) = self.stats.lock().await.get_mut(source) {
+                tracker.record_latency(latency_ms);
+            }
+        }
+    }
+
+    /*
+    A source is still connected but stopped sending frames.
+    The posture only goes stale once none of the sources has sent a recent frame,
+    until then the remaining sources keep the posture up to date on their own.
+    */
+    pub async fn mark_source_stale(&self, source: &str, silence: Duration) {
+        let server_address = {
+            let mut sources = self.sources.lock().await;
+            let Some(status) = sources.get_mut(source) else {
+                return;
+            };
+            status.stale = true;
+            status.server_address.clone()
+        };
+
+        self.report_status(
+            source,
+            ConnectionStatus {
+                connected: true,
+                message: format!("No frames received for {:.1}s", silence.as_secs_f32()),
+                server_address,
+                ..Default::default()
+            },
+        )
+        .await;
+
+        let frame_timeout =
+            Duration::from_millis(self.settings.lock().await.stream.frame_timeout_ms);
+        if !self
+            .fusion
+            .lock()
+            .await
+            .has_fresh_frames(Instant::now(), frame_timeout)
+        {
+            self.frames.push(FrameEvent::Stale { silence });
+        }
+    }
+
+    /*
+    None of the sources sent a frame recently (frozen server process, dead camera...).
+    Switch to the Stale posture so the last posture isn't displayed as live,
+    and log it so the stale time isn't counted as good or bad posture.
+    */
+    async fn mark_stream_stale(&self, silence: Duration) {
+        // Paused servers are silent on purpose
+        if *self.paused.lock().await {
+            return;
+        }
+
+        let previous_posture = {
+            let mut current = self.current_posture.lock().await;
+            std::mem::replace(&mut *current, Posture::Stale)
+        };
+
+        // Still stale from the previous connection, already logged
+        if matches!(previous_posture, Posture::Stale) {
+            return;
+        }
+
+        self.persist(PersistenceEvent::Stale {
+            previous: previous_posture.get_posture_value(),
+        })
+        .await;
+
+        // A bad posture notification would otherwise stay on screen for a posture we no longer see
+        self.notifications.push(NotificationRequest::Close);
+
+        let _ = self.app_handle.emit(
+            "stream-stale",
+            StreamStale {
+                silence_ms: silence.as_millis() as u64,
+                previous_posture: previous_posture.get_posture_value(),
+            },
+        );
+        let _ = self.app_handle.emit(
+            "posture-update",
+            PostureUpdate {
+                posture: Posture::Stale,
+                message: Posture::Stale.get_posture_message(),
+                raw_posture: None,
+                issues: Vec::new(),
+                metrics: None,
+                landmarks: PoseLandmarks::new(),
+            },
+        );
+    }
+
+    async fn mark_paused(&self) {
+        let previous_posture = {
+            let mut current = self.current_posture.lock().await;
+            std::mem::replace(&mut *current, Posture::Paused)
+        };
+        if matches!(previous_posture, Posture::Paused) {
+            return;
+        }
+
+        self.persist(PersistenceEvent::Paused {
+            previous: previous_posture.get_posture_value(),
+        })
+        .await;
+        self.notifications.push(NotificationRequest::Close);
+
+        let _ = self.app_handle.emit(
+            "posture-update",
+            PostureUpdate {
+                posture: Posture::Paused,
+                message: Posture::Paused.get_posture_message(),
+                raw_posture: None,
+                issues: Vec::new(),
+                metrics: None,
+                landmarks: PoseLandmarks::new(),
+            },
+        );
+    }
+
+    async fn handle_frame(&self, frame: FusedFrame) {
+        let Pipeline {
+            app_handle,
+            current_posture,
+            settings,
+            ..
+        } = self;
+
+        // Read for every frame, so new settings apply right away
+        let (thresholds, baseline, smoothing) = {
+            let settings = settings.lock().await;
+            (
+                settings.thresholds.clone(),
+                settings.baseline.clone().unwrap_or_default(),
+                settings.smoothing.clone(),
+            )
+        };
+        if let Some(calibration) = self.calibration.lock().await.as_mut() {
+            calibration.add_measures(&frame.measures);
+        }
+        let classifier = self.classifier.lock().await.clone();
+
+        /*
+        Classified once per frame, with the hysteresis for leaving the current posture.
+        The classified posture only replaces the current one once it lasted long enough.
+        */
+        let (posture, previous_posture, issues) = {
+            let mut current = current_posture.lock().await;
+            let issues = classifier.get_issues(
+                &frame.measures,
+                &thresholds,
+                &baseline,
+                &current,
+                smoothing.hysteresis,
+            );
+            let classified = get_primary_posture(&issues);
+
+            let previous = current.clone();
+            if self
+                .smoother
+                .lock()
+                .await
+                .accept(&previous, &classified, Instant::now(), &smoothing)
+            {
+                *current = classified;
+            }
+            (current.clone(), previous, issues)
+        };
+
+        // Keeps the posture of this frame, before the dwell time, as the raw one
+        let mut posture_update = build_update(frame.landmarks, issues.clone());
+        posture_update.message = posture.get_posture_message();
+        posture_update.posture = posture;
+
+        let posture_changed =
+            posture_update.posture.get_posture_value() != previous_posture.get_posture_value();
+
+        if posture_changed {
+            // Log posture change to database
+            self.persist(PersistenceEvent::PostureChanged {
+                current: posture_update.posture.get_posture_value(),
+                previous: previous_posture.get_posture_value(),
+                // Those of the frame that completed the dwell time
+                issues,
+            })
+            .await;
+
+            // Send notification
+            self.notifications.push(NotificationRequest::PostureChanged(
+                posture_update.posture.clone(),
+            ));
+        }
+
+        // Always emit posture update
+        let _ = app_handle.emit("posture-update", posture_update);
+    }
+
+    // Waits for the database stage rather than losing a log entry
+    async fn persist(&self, event: PersistenceEvent) {
+        self.persistence.send(event).await;
+    }
+
+    async fn run_persistence(self, mut events: mpsc::Receiver<PersistenceEvent>) {
+        while let Some(event) = events.recv().await {
+            // Write everything queued in the meantime, then refresh the session logs once
+            let mut batch = vec![event];
+            while let Ok(event) = events.try_recv() {
+                batch.push(event);
+            }
+
+            let db_lock = self.db_manager.lock().await;
+            let Some(db) = db_lock.as_ref() else {
+                continue;
+            };
+
+            for event in batch {
+                let result = match event {
+                    PersistenceEvent::PostureChanged {
+                        current,
+                        previous,
+                        issues,
+                    } => db.log_posture_change(&current, &previous, &issues),
+                    PersistenceEvent::Stale { previous } => db.log_stream_stale(&previous),
+                    PersistenceEvent::Paused { previous } => db.log_monitoring_paused(&previous),
+                };
+                if let Err(e) = result {
+                    eprintln!("Failed to log posture change: {}", e);
+                }
+            }
+
+            if let Ok(Some(logs)) = db.get_session_logs() {
+                let _ = self
+                    .app_handle
+                    .emit("session-logs-updated", SessionLogsUpdate { logs });
+            }
+        }
+    }
+
+    async fn run_notifications(self, mut requests: mpsc::Receiver<NotificationRequest>) {
+        while let Some(mut request) = requests.recv().await {
+            // The posture moved on while the previous notification was being shown
+            while let Ok(newer) = requests.try_recv() {
+                request = newer;
+            }
+
+            match request {
+                NotificationRequest::PostureChanged(posture) => {
+                    let is_good_posture = posture.get_posture_value() == "STRAIGHT";
+                    self.notification_service
+                        .notify_posture_change(&posture, is_good_posture)
+                        .await;
+
+                    let _ = self.app_handle.emit(
+                        "notification-triggered",
+                        NotificationEvent {
+                            posture: posture.get_posture_value(),
+                            message: posture.get_posture_message(),
+                            is_good_posture,
+                        },
+                    );
+                }
+                NotificationRequest::Close => self.notification_service.close_notification().await,
+            }
+        }
+    }
+
+    fn get_unix_millis() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0)
+    }
+}
@@ -11,6 +11,8 @@ pub enum Posture {
     BodyTiltLeft,
     BodyTiltRight,
     Straight,
+    Stale,
+    Paused,
     Unknown,
 }
 
@@ -26,6 +28,8 @@ impl Posture {
             Posture::BodyTiltLeft => "BODY_TILT_LEFT".to_string(),
             Posture::BodyTiltRight => "BODY_TILT_RIGHT".to_string(),
             Posture::Straight => "STRAIGHT".to_string(),
+            Posture::Stale => "STALE".to_string(),
+            Posture::Paused => "PAUSED".to_string(),
             Posture::Unknown => "UNKNOWN".to_string(),
         }
     }
@@ -41,6 +45,8 @@ impl Posture {
             Posture::BodyTiltLeft => "Body tilt left".to_string(),
             Posture::BodyTiltRight => "Body tilt right".to_string(),
             Posture::Straight => "Straight".to_string(),
+            Posture::Stale => "No data from posture server".to_string(),
+            Posture::Paused => "Monitoring paused".to_string(),
             Posture::Unknown => "Unknown".to_string(),
         }
     }
@@ -58,6 +64,8 @@ impl From<String> for Posture {
             "BODY_TILT_LEFT" => Posture::BodyTiltLeft,
             "BODY_TILT_RIGHT" => Posture::BodyTiltRight,
             "STRAIGHT" => Posture::Straight,
+            "STALE" => Posture::Stale,
+            "PAUSED" => Posture::Paused,
             "UNKNOWN" => Posture::Unknown,
             _ => Posture::Unknown,
         }
@@ -76,6 +84,8 @@ impl From<&str> for Posture {
             "BODY_TILT_LEFT" => Posture::BodyTiltLeft,
             "BODY_TILT_RIGHT" => Posture::BodyTiltRight,
             "STRAIGHT" => Posture::Straight,
+            "STALE" => Posture::Stale,
+            "PAUSED" => Posture::Paused,
             "UNKNOWN" => Posture::Unknown,
             _ => Posture::Unknown,
         }
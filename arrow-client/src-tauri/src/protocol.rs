@@ -0,0 +1,380 @@
+use crate::events::Point3D;
+use crate::landmarks::{Landmark, PoseLandmarks};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+/*
+The posture server can send metrics in two formats, one frame per line:
+
+- Legacy pipe format: 16 `|`-separated floats, the x, y, z and visibility
+  of the left ear, right ear, left shoulder and right shoulder in that order.
+- JSON-lines format: a self-describing object with a protocol version and
+  named landmarks, e.g.
+  {"version":1,"landmarks":{"left_ear":{"x":0.3,"y":0.2,"z":0.5,"visibility":0.95},...}}
+  Landmarks use the MediaPipe names, any of the 33 may be sent or omitted
+  and unknown names are ignored.
+  Optional `seq` (frame counter incremented by one per frame) and `captured_at`
+  (unix timestamp in milliseconds of the camera capture) fields let the client
+  spot dropped frames and measure the latency.
+  Any other top-level field is ignored.
+
+The format is detected per line so old servers keep working unchanged.
+Every coordinate must be a finite number and every visibility must be
+between 0 and 1, lines that break these rules are rejected as a whole.
+
+Servers that support the handshake answer the client hello with a single
+{"type":"hello",...} line describing themselves before streaming metrics,
+or with {"type":"error","code":...,"message":...} if they refuse the client.
+Servers that don't are treated as legacy servers.
+
+Once connected, the client can control the server with command lines such as
+{"type":"command","id":1,"command":"set_frame_rate","frame_rate":15}
+which the server answers in the stream with {"type":"ack","id":1,"ok":true,"message":"..."}.
+The hello lists the commands a server accepts, none are sent to servers that don't list them.
+
+Servers can also announce themselves to the clients on the local network by sending
+{"type":"beacon","server_name":...,"server_version":...,"protocol_version":1,"port":9876,"tls":false}
+UDP datagrams every few seconds, see `discovery`.
+*/
+pub const PROTOCOL_VERSION: u32 = 1;
+
+// Landmarks the classifier cannot work without
+pub const REQUIRED_LANDMARKS: [&str; 4] =
+    ["left_ear", "right_ear", "left_shoulder", "right_shoulder"];
+
+const PIPE_FIELD_COUNT: usize = 16;
+const PIPE_LANDMARKS: [Landmark; 4] = [
+    Landmark::LeftEar,
+    Landmark::RightEar,
+    Landmark::LeftShoulder,
+    Landmark::RightShoulder,
+];
+const POINT_FIELDS: [&str; 4] = ["x", "y", "z", "visibility"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WireFormat {
+    Pipe,
+    Json,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    UnknownFormat,
+    FieldCount { expected: usize, found: usize },
+    InvalidNumber { field: String, value: String },
+    NonFinite { field: String },
+    VisibilityOutOfRange { landmark: String, value: f32 },
+    InvalidJson(String),
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnknownFormat => write!(f, "unknown line format"),
+            ParseError::FieldCount { expected, found } => {
+                write!(f, "expected {} fields, found {}", expected, found)
+            }
+            ParseError::InvalidNumber { field, value } => {
+                write!(f, "invalid number {:?} for {}", value, field)
+            }
+            ParseError::NonFinite { field } => write!(f, "non-finite value for {}", field),
+            ParseError::VisibilityOutOfRange { landmark, value } => {
+                write!(
+                    f,
+                    "visibility {} of {} is not between 0 and 1",
+                    value, landmark
+                )
+            }
+            ParseError::InvalidJson(e) => write!(f, "invalid JSON frame: {}", e),
+            ParseError::UnsupportedVersion(version) => {
+                write!(f, "unsupported protocol version {}", version)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct JsonFrame {
+    pub version: u32,
+    pub landmarks: HashMap<String, Point3D>,
+    #[serde(default)]
+    pub seq: Option<u64>,
+    #[serde(default)]
+    pub captured_at: Option<f64>,
+}
+
+// Optional fields of a metric frame, only sent in the JSON format
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FrameMetadata {
+    pub seq: Option<u64>,
+    // Unix timestamp in milliseconds
+    pub captured_at: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerInfo {
+    pub server_name: String,
+    pub server_version: String,
+    pub protocol_version: u32,
+    pub frame_rate: f32,
+    pub landmarks: Vec<String>,
+    // Names of the control commands the server accepts
+    #[serde(default)]
+    pub commands: Vec<String>,
+}
+
+// Announcement of a server listening for TCP connections on `port`, at the address it was sent from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServerBeacon {
+    pub server_name: String,
+    pub server_version: String,
+    pub protocol_version: u32,
+    pub port: u16,
+    #[serde(default)]
+    pub tls: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum BeaconMessage {
+    Beacon(ServerBeacon),
+}
+
+// Sent by the server instead of a hello when it refuses the client, e.g. for a bad token
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerError {
+    pub code: String,
+    pub message: String,
+}
+
+// Answer of the server to a control command, matched to the command by its id
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandAck {
+    pub id: u64,
+    pub ok: bool,
+    #[serde(default)]
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    Hello(ServerInfo),
+    Error(ServerError),
+    Ack(CommandAck),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum ControlCommand {
+    // Stops capturing and releases the camera until resumed
+    Pause,
+    Resume,
+    SetFrameRate { frame_rate: f32 },
+    StartCalibration,
+    Shutdown,
+}
+
+impl ControlCommand {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            ControlCommand::Pause => "pause",
+            ControlCommand::Resume => "resume",
+            ControlCommand::SetFrameRate { .. } => "set_frame_rate",
+            ControlCommand::StartCalibration => "start_calibration",
+            ControlCommand::Shutdown => "shutdown",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClientCommand {
+    #[serde(rename = "type")]
+    message_type: &'static str,
+    pub id: u64,
+    #[serde(flatten)]
+    pub command: ControlCommand,
+}
+
+impl ClientCommand {
+    pub fn new(id: u64, command: ControlCommand) -> Self {
+        Self {
+            message_type: "command",
+            id,
+            command,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClientHello {
+    #[serde(rename = "type")]
+    message_type: &'static str,
+    pub client_name: &'static str,
+    pub client_version: &'static str,
+    pub protocol_version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth_token: Option<String>,
+}
+
+impl ClientHello {
+    pub fn new(auth_token: Option<String>) -> Self {
+        Self {
+            message_type: "hello",
+            client_name: "arrow",
+            client_version: env!("CARGO_PKG_VERSION"),
+            protocol_version: PROTOCOL_VERSION,
+            auth_token,
+        }
+    }
+}
+
+// Handshake and acknowledgement messages only, metric frames are handled by `parse_frame`
+pub fn parse_server_message(line: &str) -> Option<ServerMessage> {
+    if detect_format(line) != Some(WireFormat::Json) {
+        return None;
+    }
+
+    serde_json::from_str(line).ok()
+}
+
+// Datagrams that aren't beacons are ignored, other apps may use the same port
+pub fn parse_beacon(datagram: &[u8]) -> Option<ServerBeacon> {
+    match serde_json::from_slice(datagram).ok()? {
+        BeaconMessage::Beacon(beacon) => Some(beacon),
+    }
+}
+
+pub fn check_protocol_version(protocol_version: u32) -> Result<(), String> {
+    if protocol_version == 0 || protocol_version > PROTOCOL_VERSION {
+        return Err(format!(
+            "protocol version {} is not supported (expected {})",
+            protocol_version, PROTOCOL_VERSION
+        ));
+    }
+
+    Ok(())
+}
+
+pub fn check_compatibility(info: &ServerInfo) -> Result<(), String> {
+    check_protocol_version(info.protocol_version)?;
+
+    let missing: Vec<&str> = REQUIRED_LANDMARKS
+        .iter()
+        .filter(|name| !info.landmarks.iter().any(|landmark| landmark == *name))
+        .copied()
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!("missing landmarks: {}", missing.join(", ")));
+    }
+
+    Ok(())
+}
+
+pub fn detect_format(line: &str) -> Option<WireFormat> {
+    let line = line.trim_start();
+    if line.starts_with('{') {
+        Some(WireFormat::Json)
+    } else if line.contains('|') {
+        Some(WireFormat::Pipe)
+    } else {
+        None
+    }
+}
+
+pub fn parse_frame(line: &str) -> Result<(PoseLandmarks, FrameMetadata), ParseError> {
+    match detect_format(line) {
+        Some(WireFormat::Pipe) => Ok((parse_pipe_line(line)?, FrameMetadata::default())),
+        Some(WireFormat::Json) => parse_json_line(line),
+        None => Err(ParseError::UnknownFormat),
+    }
+}
+
+fn parse_pipe_line(line: &str) -> Result<PoseLandmarks, ParseError> {
+    let parts: Vec<&str> = line.split('|').collect();
+    if parts.len() != PIPE_FIELD_COUNT {
+        return Err(ParseError::FieldCount {
+            expected: PIPE_FIELD_COUNT,
+            found: parts.len(),
+        });
+    }
+
+    let mut values = [0.0f32; PIPE_FIELD_COUNT];
+    for (index, part) in parts.iter().enumerate() {
+        let field = format!(
+            "{}.{}",
+            PIPE_LANDMARKS[index / 4].get_name(),
+            POINT_FIELDS[index % 4]
+        );
+        values[index] = part
+            .trim()
+            .parse::<f32>()
+            .map_err(|_| ParseError::InvalidNumber {
+                field,
+                value: part.to_string(),
+            })?;
+    }
+
+    let mut landmarks = PoseLandmarks::new();
+    for (index, landmark) in PIPE_LANDMARKS.iter().enumerate() {
+        let point = Point3D {
+            x: values[index * 4],
+            y: values[index * 4 + 1],
+            z: values[index * 4 + 2],
+            visibility: values[index * 4 + 3],
+        };
+        validate_point(*landmark, &point)?;
+        landmarks.insert(*landmark, point);
+    }
+
+    Ok(landmarks)
+}
+
+fn parse_json_line(line: &str) -> Result<(PoseLandmarks, FrameMetadata), ParseError> {
+    let frame: JsonFrame =
+        serde_json::from_str(line).map_err(|e| ParseError::InvalidJson(e.to_string()))?;
+
+    // Newer versions may change the meaning of existing fields
+    if frame.version == 0 || frame.version > PROTOCOL_VERSION {
+        return Err(ParseError::UnsupportedVersion(frame.version));
+    }
+
+    let mut landmarks = PoseLandmarks::new();
+    for (name, point) in frame.landmarks {
+        if let Some(landmark) = Landmark::from_name(&name) {
+            validate_point(landmark, &point)?;
+            landmarks.insert(landmark, point);
+        }
+    }
+
+    let metadata = FrameMetadata {
+        seq: frame.seq,
+        captured_at: frame.captured_at,
+    };
+
+    Ok((landmarks, metadata))
+}
+
+fn validate_point(landmark: Landmark, point: &Point3D) -> Result<(), ParseError> {
+    let values = [point.x, point.y, point.z, point.visibility];
+    for (value, field) in values.iter().zip(POINT_FIELDS) {
+        if !value.is_finite() {
+            return Err(ParseError::NonFinite {
+                field: format!("{}.{}", landmark.get_name(), field),
+            });
+        }
+    }
+
+    if !(0.0..=1.0).contains(&point.visibility) {
+        return Err(ParseError::VisibilityOutOfRange {
+            landmark: landmark.get_name().to_string(),
+            value: point.visibility,
+        });
+    }
+
+    Ok(())
+}
@@ -0,0 +1,44 @@
+use crate::settings::ReconnectPolicy;
+use std::time::Duration;
+
+// Tracks consecutive failed connection attempts and computes the delay before the next one
+#[derive(Debug, Default)]
+pub struct Backoff {
+    attempt: u32,
+}
+
+impl Backoff {
+    pub fn get_attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+    }
+
+    /*
+    Registers a failed attempt and returns how long to wait before the next one,
+    or None if the policy does not allow any more attempts.
+    The delay grows exponentially from `initial_delay_ms` up to `max_delay_ms`,
+    then a random jitter of +/- `jitter` (as a fraction of the delay) is applied
+    so that several clients don't hammer a restarting server in lockstep.
+    */
+    pub fn next_delay(&mut self, policy: &ReconnectPolicy) -> Option<Duration> {
+        self.attempt = self.attempt.saturating_add(1);
+
+        if let Some(max_attempts) = policy.max_attempts {
+            if self.attempt >= max_attempts {
+                return None;
+            }
+        }
+
+        let exponent = (self.attempt - 1).min(i32::MAX as u32) as i32;
+        let delay = (policy.initial_delay_ms as f64 * policy.multiplier.powi(exponent))
+            .min(policy.max_delay_ms as f64);
+        let jitter = delay * policy.jitter * (fastrand::f64() * 2.0 - 1.0);
+
+        Some(Duration::from_millis(
+            (delay + jitter).clamp(0.0, policy.max_delay_ms as f64) as u64,
+        ))
+    }
+}
@@ -0,0 +1,280 @@
+use crate::events::{ServerProcessState, ServerProcessStatus};
+use crate::reconnect::Backoff;
+use crate::settings::ServerProcessSettings;
+use std::io;
+use std::path::Path;
+use std::process::{ExitStatus, Stdio};
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::{Child, Command};
+use tokio::sync::{oneshot, Mutex};
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, Duration, Instant};
+
+// A server running for this long is healthy again, its next crash restarts it right away
+const STABLE_RUN: Duration = Duration::from_secs(30);
+
+/*
+Starts the posture server configured in the settings as a child process and keeps it running:
+its output goes to the app log, it is restarted with a backoff when it exits,
+and it is killed when the supervisor is stopped.
+Every change of its health is emitted as a `server-process-status` event.
+*/
+pub struct ServerSupervisor {
+    status: StatusReporter,
+    task: Mutex<Option<SupervisorTask>>,
+}
+
+struct SupervisorTask {
+    shutdown: oneshot::Sender<()>,
+    handle: JoinHandle<()>,
+}
+
+#[derive(Clone)]
+struct StatusReporter {
+    app_handle: AppHandle,
+    status: Arc<Mutex<ServerProcessStatus>>,
+}
+
+impl StatusReporter {
+    async fn update(&self, update: impl FnOnce(&mut ServerProcessStatus)) {
+        let status = {
+            let mut status = self.status.lock().await;
+            update(&mut status);
+            status.clone()
+        };
+        let _ = self.app_handle.emit("server-process-status", status);
+    }
+
+    async fn set_state(&self, state: ServerProcessState, message: String) {
+        self.update(|status| {
+            status.state = state;
+            status.message = message;
+            if state != ServerProcessState::Running {
+                status.pid = None;
+            }
+        })
+        .await;
+    }
+}
+
+impl ServerSupervisor {
+    pub fn new(app_handle: AppHandle) -> Self {
+        Self {
+            status: StatusReporter {
+                app_handle,
+                status: Arc::new(Mutex::new(ServerProcessStatus {
+                    state: ServerProcessState::Disabled,
+                    pid: None,
+                    restarts: 0,
+                    last_exit: None,
+                    last_error_output: None,
+                    message: "Server not started by the app".to_string(),
+                })),
+            },
+            task: Mutex::new(None),
+        }
+    }
+
+    // Starts the server with these settings, the one already running is stopped first
+    pub async fn start(&self, settings: ServerProcessSettings) {
+        let mut task = self.task.lock().await;
+        if let Some(running) = task.take() {
+            running.stop().await;
+        }
+
+        if !settings.enabled {
+            self.status
+                .set_state(
+                    ServerProcessState::Disabled,
+                    "Server not started by the app".to_string(),
+                )
+                .await;
+            return;
+        }
+
+        self.status
+            .update(|status| {
+                status.restarts = 0;
+                status.last_exit = None;
+                status.last_error_output = None;
+            })
+            .await;
+
+        let (shutdown, shutdown_receiver) = oneshot::channel();
+        let handle = tokio::spawn(supervise(settings, self.status.clone(), shutdown_receiver));
+        *task = Some(SupervisorTask { shutdown, handle });
+    }
+
+    // Kills the server and waits for it to exit
+    pub async fn stop(&self) {
+        if let Some(running) = self.task.lock().await.take() {
+            running.stop().await;
+        }
+    }
+
+    pub async fn get_status(&self) -> ServerProcessStatus {
+        self.status.status.lock().await.clone()
+    }
+}
+
+impl SupervisorTask {
+    async fn stop(self) {
+        let _ = self.shutdown.send(());
+        let _ = self.handle.await;
+    }
+}
+
+async fn supervise(
+    settings: ServerProcessSettings,
+    status: StatusReporter,
+    mut shutdown: oneshot::Receiver<()>,
+) {
+    let mut backoff = Backoff::default();
+
+    loop {
+        status
+            .set_state(
+                ServerProcessState::Starting,
+                format!("Starting {}", settings.script_path),
+            )
+            .await;
+
+        let started = Instant::now();
+        let last_exit = match spawn_server(&settings) {
+            Ok(mut child) => {
+                forward_output(&mut child, &status);
+                let pid = child.id();
+                status
+                    .update(|status| {
+                        status.state = ServerProcessState::Running;
+                        status.pid = pid;
+                        status.message = "Server running".to_string();
+                    })
+                    .await;
+
+                tokio::select! {
+                    result = child.wait() => describe_exit(result),
+                    // Also when the supervisor is dropped without being stopped
+                    _ = &mut shutdown => {
+                        // Kills the server and waits for it, so it never outlives the app
+                        let _ = child.kill().await;
+                        status
+                            .set_state(ServerProcessState::Stopped, "Server stopped".to_string())
+                            .await;
+                        return;
+                    }
+                }
+            }
+            Err(e) => format!("Failed to start server: {}", e),
+        };
+        eprintln!("[server] {}", last_exit);
+
+        if started.elapsed() >= STABLE_RUN {
+            backoff.reset();
+        }
+        let Some(delay) = backoff.next_delay(&settings.restart) else {
+            status
+                .update(|status| {
+                    status.state = ServerProcessState::Failed;
+                    status.pid = None;
+                    status.message = format!(
+                        "Gave up restarting the server after {} attempts: {}",
+                        backoff.get_attempt(),
+                        last_exit
+                    );
+                    status.last_exit = Some(last_exit);
+                })
+                .await;
+            return;
+        };
+
+        status
+            .update(|status| {
+                status.state = ServerProcessState::Restarting;
+                status.pid = None;
+                status.restarts = backoff.get_attempt();
+                status.message =
+                    format!("{}, restarting in {:.1}s", last_exit, delay.as_secs_f32());
+                status.last_exit = Some(last_exit);
+            })
+            .await;
+
+        tokio::select! {
+            _ = sleep(delay) => {}
+            _ = &mut shutdown => {
+                status
+                    .set_state(ServerProcessState::Stopped, "Server stopped".to_string())
+                    .await;
+                return;
+            }
+        }
+    }
+}
+
+// Spawns `<interpreter> <script_path> <args...>` with its output piped, see `forward_output`
+pub fn spawn_server(settings: &ServerProcessSettings) -> io::Result<Child> {
+    let script = Path::new(&settings.script_path);
+    let mut command = Command::new(&settings.interpreter);
+    command
+        .arg(script)
+        .args(&settings.args)
+        // Python buffers its output when it isn't a terminal, which would delay the log
+        .env("PYTHONUNBUFFERED", "1")
+        .envs(&settings.env)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        // Last resort if the supervisor task is aborted before killing the server
+        .kill_on_drop(true);
+
+    match &settings.working_dir {
+        Some(working_dir) => {
+            command.current_dir(working_dir);
+        }
+        None => {
+            if let Some(parent) = script.parent().filter(|parent| parent.is_dir()) {
+                command.current_dir(parent);
+            }
+        }
+    }
+
+    command.spawn()
+}
+
+// Copies the output of the server to the app log, the last stderr line is kept in the status
+fn forward_output(child: &mut Child, status: &StatusReporter) {
+    if let Some(stdout) = child.stdout.take() {
+        tokio::spawn(forward_lines(stdout, None));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        tokio::spawn(forward_lines(stderr, Some(status.clone())));
+    }
+}
+
+async fn forward_lines(output: impl AsyncRead + Unpin, status: Option<StatusReporter>) {
+    let mut lines = BufReader::new(output).lines();
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        match &status {
+            Some(status) => {
+                eprintln!("[server] {}", line);
+                if !line.trim().is_empty() {
+                    status.status.lock().await.last_error_output = Some(line);
+                }
+            }
+            None => println!("[server] {}", line),
+        }
+    }
+}
+
+fn describe_exit(result: io::Result<ExitStatus>) -> String {
+    match result {
+        Ok(exit_status) => match exit_status.code() {
+            Some(code) => format!("Server exited with code {}", code),
+            None => "Server was killed".to_string(),
+        },
+        Err(e) => format!("Failed to wait for server: {}", e),
+    }
+}
@@ -0,0 +1,626 @@
+use crate::capture::CaptureRecording;
+use crate::events::{ConnectionStatus, StreamDiagnostics};
+use crate::protocol::{
+    self, ClientCommand, ClientHello, CommandAck, ControlCommand, ParseError, ServerMessage,
+};
+use crate::reconnect::Backoff;
+use crate::settings::{CameraView, ServerAddress, Settings, Transport};
+use crate::source::{FrameSink, PostureSource};
+use crate::transport::{self, PostureConnection};
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::io::ErrorKind;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::{mpsc, oneshot, watch, Mutex};
+use tokio::time::{interval_at, sleep, sleep_until, timeout, Duration, Instant};
+
+// How long to wait for the server hello before assuming a legacy server
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+// How often the line counters are emitted while connected
+const DIAGNOSTICS_INTERVAL: Duration = Duration::from_secs(10);
+// How long to wait for the server to acknowledge a control command
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub type CommandReply = oneshot::Receiver<Result<String, String>>;
+type ReplySender = oneshot::Sender<Result<String, String>>;
+
+// A control command for the server the source is connected to, and where to send its outcome
+pub struct CommandRequest {
+    pub command: ControlCommand,
+    pub reply: ReplySender,
+}
+
+impl CommandRequest {
+    pub fn new(command: ControlCommand) -> (Self, CommandReply) {
+        let (reply, receiver) = oneshot::channel();
+        (Self { command, reply }, receiver)
+    }
+}
+
+// Returns the acknowledgement message of the server, or why the command failed
+pub async fn wait_for_ack(reply: CommandReply) -> Result<String, String> {
+    match timeout(COMMAND_TIMEOUT, reply).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(_)) => {
+            Err("The connection was closed before the command was acknowledged".to_string())
+        }
+        Err(_) => Err("The posture server did not acknowledge the command in time".to_string()),
+    }
+}
+
+// Commands sent on the current connection and waiting for their acknowledgement
+#[derive(Default)]
+struct PendingCommands {
+    next_id: u64,
+    // No reply for the pauses and resumes sent when the monitoring is paused or resumed
+    waiting: HashMap<u64, (ControlCommand, Option<ReplySender>)>,
+}
+
+// Handles shared by all the server sources of a client
+#[derive(Clone)]
+pub struct ServerSourceContext {
+    pub diagnostics: Arc<Mutex<BTreeMap<String, StreamDiagnostics>>>,
+    // Set while the received lines are being recorded to a capture file
+    pub recorder: Arc<Mutex<Option<CaptureRecording>>>,
+    pub settings: Arc<Mutex<Settings>>,
+    // While true, the servers are asked to pause and release their camera
+    pub paused: watch::Receiver<bool>,
+}
+
+/*
+A posture server speaking the line protocol, over any transport including capture replays.
+Handles the handshake, reconnections and stale streams, and turns metric lines into frames.
+Control commands received while connected are forwarded to the server.
+*/
+pub struct ServerSource {
+    name: String,
+    weight: f32,
+    view: CameraView,
+    server_address: watch::Receiver<ServerAddress>,
+    commands: mpsc::Receiver<CommandRequest>,
+    context: ServerSourceContext,
+}
+
+impl ServerSource {
+    pub fn new(
+        name: &str,
+        weight: f32,
+        view: CameraView,
+        server_address: watch::Receiver<ServerAddress>,
+        commands: mpsc::Receiver<CommandRequest>,
+        context: ServerSourceContext,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            weight,
+            view,
+            server_address,
+            commands,
+            context,
+        }
+    }
+
+    fn get_error_kind(error: &(dyn Error + Send + Sync + 'static)) -> String {
+        match error.downcast_ref::<std::io::Error>() {
+            Some(io_error) => format!("{:?}", io_error.kind()),
+            None => "Protocol".to_string(),
+        }
+    }
+
+    fn get_unix_millis() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0)
+    }
+
+    async fn handle_connection(
+        mut connection: PostureConnection,
+        address: &ServerAddress,
+        sink: &FrameSink,
+        context: &ServerSourceContext,
+        server_address: &mut watch::Receiver<ServerAddress>,
+        commands: &mut mpsc::Receiver<CommandRequest>,
+        backoff: &mut Backoff,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        // Introduce ourselves, legacy servers never read from the socket and ignore it.
+        // A hand-edited settings file can skip the validation, the token still never goes in clear
+        let auth_token = address
+            .auth_token
+            .clone()
+            .filter(|_| address.is_confidential());
+        let hello = serde_json::to_string(&ClientHello::new(auth_token))?;
+        connection.write_line(&hello).await?;
+
+        let mut last_frame = Instant::now();
+        let mut stale = false;
+        let mut paused = context.paused.clone();
+        let mut pending = PendingCommands::default();
+        // Commands accepted by the server, a replay has no server to control
+        let mut supported_commands = Vec::new();
+        let mut diagnostics_interval =
+            interval_at(Instant::now() + DIAGNOSTICS_INTERVAL, DIAGNOSTICS_INTERVAL);
+
+        let pending_line = tokio::select! {
+            result = Self::read_handshake(&mut connection) => result?,
+            _ = server_address.changed() => return Ok(()),
+        };
+
+        if let Some(first_line) = pending_line {
+            Self::record_line(&first_line, sink, context).await;
+
+            match protocol::parse_server_message(&first_line) {
+                Some(ServerMessage::Hello(info)) => {
+                    protocol::check_compatibility(&info)
+                        .map_err(|e| format!("Incompatible posture server: {}", e))?;
+
+                    println!(
+                        "Connected to {} {} (protocol v{})",
+                        info.server_name, info.server_version, info.protocol_version
+                    );
+                    if address.transport != Transport::Replay {
+                        supported_commands = info.commands.clone();
+                    }
+                    sink.set_server_info(Some(info)).await;
+                    // Only an accepted handshake counts as a successful attempt
+                    backoff.reset();
+                }
+                Some(ServerMessage::Error(error)) => {
+                    return Err(format!(
+                        "Server refused the connection: {} ({})",
+                        error.message, error.code
+                    )
+                    .into());
+                }
+                Some(ServerMessage::Ack(_)) | None => {
+                    // No handshake: a legacy server that starts streaming metrics right away
+                    if Self::handle_line(&first_line, address, sink, context).await {
+                        last_frame = Instant::now();
+                        backoff.reset();
+                    }
+                }
+            }
+        }
+
+        // The monitoring was paused before this connection
+        if *paused.borrow_and_update() {
+            Self::send_command(
+                &mut connection,
+                &mut pending,
+                &supported_commands,
+                ControlCommand::Pause,
+                None,
+                address,
+                sink,
+            )
+            .await?;
+        }
+
+        loop {
+            let stream_settings = context.settings.lock().await.stream.clone();
+            // A paused server sends no frames, it isn't stale
+            let monitoring_paused = *paused.borrow();
+            // Once stale, keep waiting until the longer timeout recycles the connection
+            let deadline = if stale {
+                last_frame + Duration::from_millis(stream_settings.reconnect_timeout_ms)
+            } else {
+                last_frame + Duration::from_millis(stream_settings.frame_timeout_ms)
+            };
+
+            let read_result = tokio::select! {
+                result = connection.read_line() => result,
+                _ = sleep_until(deadline), if !monitoring_paused => {
+                    if stale {
+                        return Err(Box::new(std::io::Error::new(
+                            ErrorKind::TimedOut,
+                            format!(
+                                "no frames received for {:.1}s",
+                                last_frame.elapsed().as_secs_f32()
+                            ),
+                        )));
+                    }
+
+                    stale = true;
+                    sink.mark_stale(last_frame.elapsed()).await;
+                    continue;
+                }
+                _ = diagnostics_interval.tick() => {
+                    let diagnostics = context.diagnostics.lock().await.get(sink.get_source_name()).cloned();
+                    if let Some(diagnostics) = diagnostics {
+                        sink.report_diagnostics(diagnostics);
+                    }
+                    continue;
+                }
+                Some(request) = commands.recv() => {
+                    // Nobody is waiting for the outcome anymore, e.g. queued while disconnected
+                    if !request.reply.is_closed() {
+                        Self::send_command(
+                            &mut connection,
+                            &mut pending,
+                            &supported_commands,
+                            request.command,
+                            Some(request.reply),
+                            address,
+                            sink,
+                        )
+                        .await?;
+                    }
+                    continue;
+                }
+                Ok(()) = paused.changed() => {
+                    let command = if *paused.borrow_and_update() {
+                        ControlCommand::Pause
+                    } else {
+                        last_frame = Instant::now();
+                        stale = false;
+                        ControlCommand::Resume
+                    };
+                    Self::send_command(
+                        &mut connection,
+                        &mut pending,
+                        &supported_commands,
+                        command,
+                        None,
+                        address,
+                        sink,
+                    )
+                    .await?;
+                    continue;
+                }
+                // Drop the connection so the caller reconnects to the new address
+                _ = server_address.changed() => break,
+            };
+
+            match read_result {
+                Ok(None) => {
+                    // EOF - server closed connection
+                    break;
+                }
+                Ok(Some(line)) => {
+                    Self::record_line(&line, sink, context).await;
+
+                    if let Some(ServerMessage::Ack(ack)) = protocol::parse_server_message(&line) {
+                        Self::handle_ack(ack, &mut pending, address, sink).await;
+                        continue;
+                    }
+
+                    if Self::handle_line(&line, address, sink, context).await {
+                        last_frame = Instant::now();
+                        stale = false;
+                        backoff.reset();
+                    }
+                }
+                Err(e) => {
+                    return Err(Box::new(e));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /*
+    Waits for the first line sent by the server.
+    Returns None if the server stays silent for HANDSHAKE_TIMEOUT,
+    and an error if it closes the connection before saying anything.
+    */
+    async fn read_handshake(
+        connection: &mut PostureConnection,
+    ) -> Result<Option<String>, Box<dyn Error + Send + Sync>> {
+        match timeout(HANDSHAKE_TIMEOUT, connection.read_line()).await {
+            Ok(Ok(None)) => Err("server closed the connection during handshake".into()),
+            Ok(Ok(Some(line))) => Ok(Some(line)),
+            Ok(Err(e)) => Err(Box::new(e)),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /*
+    Sends a command to the server, or fails it right away if the server doesn't accept it.
+    Without a reply, the command was sent for the paused monitoring
+    and its outcome is reported in the connection status.
+    */
+    async fn send_command(
+        connection: &mut PostureConnection,
+        pending: &mut PendingCommands,
+        supported_commands: &[String],
+        command: ControlCommand,
+        reply: Option<ReplySender>,
+        address: &ServerAddress,
+        sink: &FrameSink,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let name = command.get_name();
+        if !supported_commands.iter().any(|supported| supported == name) {
+            let error = format!("The posture server does not support the {} command", name);
+            match reply {
+                Some(reply) => {
+                    let _ = reply.send(Err(error));
+                }
+                None => {
+                    // Nothing to resume on a server that couldn't pause
+                    let result = if command == ControlCommand::Resume {
+                        Ok(String::new())
+                    } else {
+                        Err(error)
+                    };
+                    Self::report_command_status(&command, result, address, sink).await;
+                }
+            }
+            return Ok(());
+        }
+
+        pending.next_id += 1;
+        let id = pending.next_id;
+        let line = serde_json::to_string(&ClientCommand::new(id, command.clone()))?;
+        connection.write_line(&line).await?;
+        pending.waiting.insert(id, (command, reply));
+
+        Ok(())
+    }
+
+    async fn handle_ack(
+        ack: CommandAck,
+        pending: &mut PendingCommands,
+        address: &ServerAddress,
+        sink: &FrameSink,
+    ) {
+        let Some((command, reply)) = pending.waiting.remove(&ack.id) else {
+            return;
+        };
+
+        let result = if ack.ok {
+            Ok(ack.message)
+        } else {
+            Err(format!(
+                "The posture server rejected the {} command: {}",
+                command.get_name(),
+                ack.message
+            ))
+        };
+
+        match reply {
+            Some(reply) => {
+                let _ = reply.send(result);
+            }
+            None => Self::report_command_status(&command, result, address, sink).await,
+        }
+    }
+
+    async fn report_command_status(
+        command: &ControlCommand,
+        result: Result<String, String>,
+        address: &ServerAddress,
+        sink: &FrameSink,
+    ) {
+        let message = match (command, result) {
+            (ControlCommand::Pause, Ok(_)) => "Monitoring paused, camera released".to_string(),
+            (ControlCommand::Pause, Err(e)) => {
+                format!("Monitoring paused, but the camera is still in use: {}", e)
+            }
+            (ControlCommand::Resume, Ok(_)) => "Monitoring resumed".to_string(),
+            (ControlCommand::Resume, Err(e)) => {
+                format!("Monitoring resumed, but the posture server did not: {}", e)
+            }
+            (_, Ok(message)) | (_, Err(message)) => message,
+        };
+
+        sink.report_status(ConnectionStatus {
+            connected: true,
+            message,
+            server_address: address.to_string(),
+            ..Default::default()
+        })
+        .await;
+    }
+
+    async fn record_line(line: &str, sink: &FrameSink, context: &ServerSourceContext) {
+        if let Some(recording) = context.recorder.lock().await.as_ref() {
+            recording.record(sink.get_source_name(), line);
+        }
+    }
+
+    // Returns whether the line contained a metrics frame
+    async fn handle_line(
+        line: &str,
+        address: &ServerAddress,
+        sink: &FrameSink,
+        context: &ServerSourceContext,
+    ) -> bool {
+        let parse_result = protocol::parse_frame(line);
+
+        if let Some(diagnostics) = context
+            .diagnostics
+            .lock()
+            .await
+            .get_mut(sink.get_source_name())
+        {
+            match &parse_result {
+                Ok(_) => diagnostics.good_lines += 1,
+                Err(ParseError::UnknownFormat) => diagnostics.unknown_format_lines += 1,
+                Err(_) => diagnostics.rejected_lines += 1,
+            }
+            if let Err(e) = &parse_result {
+                diagnostics.last_error = Some(e.to_string());
+            }
+        }
+
+        let Ok((landmarks, mut metadata)) = parse_result else {
+            return false;
+        };
+
+        // Replayed frames were captured long ago, their latency means nothing
+        if address.transport == Transport::Replay {
+            metadata.captured_at = None;
+        }
+
+        sink.push_frame(landmarks, metadata).await;
+
+        true
+    }
+}
+
+impl PostureSource for ServerSource {
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn get_weight(&self) -> f32 {
+        self.weight
+    }
+
+    fn get_view(&self) -> CameraView {
+        self.view
+    }
+
+    // Connects and keeps reconnecting until the sender of the address is dropped,
+    // a replay stops at the end of the capture until the address changes
+    async fn run(self, sink: FrameSink) {
+        let ServerSource {
+            mut server_address,
+            mut commands,
+            context,
+            ..
+        } = self;
+        let source = sink.get_source_name().to_string();
+        let mut backoff = Backoff::default();
+
+        loop {
+            let address = server_address.borrow_and_update().clone();
+
+            let (message, last_error_kind) = match transport::connect(&address).await {
+                Ok(connection) => {
+                    // The counters of the previous connection are reported right away as reset
+                    let diagnostics = StreamDiagnostics::new(&source, &address);
+                    context
+                        .diagnostics
+                        .lock()
+                        .await
+                        .insert(source.clone(), diagnostics.clone());
+                    sink.report_diagnostics(diagnostics);
+
+                    sink.report_status(ConnectionStatus {
+                        connected: true,
+                        message: "Connected to posture server".to_string(),
+                        server_address: address.to_string(),
+                        ..Default::default()
+                    })
+                    .await;
+
+                    let result = Self::handle_connection(
+                        connection,
+                        &address,
+                        &sink,
+                        &context,
+                        &mut server_address,
+                        &mut commands,
+                        &mut backoff,
+                    )
+                    .await;
+
+                    sink.disconnected().await;
+
+                    // The address changed while connected: reconnect right away
+                    let new_address = server_address.borrow().clone();
+                    if new_address != address {
+                        sink.report_status(ConnectionStatus {
+                            connected: false,
+                            message: "Server address changed. Reconnecting...".to_string(),
+                            server_address: new_address.to_string(),
+                            ..Default::default()
+                        })
+                        .await;
+                        continue;
+                    }
+
+                    match result {
+                        // The whole capture was played, playing it again would log it twice
+                        Ok(()) if address.transport == Transport::Replay => {
+                            sink.report_status(ConnectionStatus {
+                                connected: false,
+                                message: "Replay finished".to_string(),
+                                server_address: address.to_string(),
+                                ..Default::default()
+                            })
+                            .await;
+
+                            // Only a new server address starts another replay or connection
+                            if server_address.changed().await.is_err() {
+                                break;
+                            }
+                            backoff.reset();
+                            continue;
+                        }
+                        Ok(()) => ("Disconnected from server".to_string(), None),
+                        Err(e) => {
+                            eprintln!("Connection error ({}): {}", source, e);
+                            (
+                                format!("Disconnected from server: {}", e),
+                                Some(Self::get_error_kind(e.as_ref())),
+                            )
+                        }
+                    }
+                }
+                Err(e) => (
+                    format!("Connection failed: {}", e),
+                    Some(format!("{:?}", e.kind())),
+                ),
+            };
+
+            let policy = context.settings.lock().await.reconnect.clone();
+            match backoff.next_delay(&policy) {
+                Some(delay) => {
+                    sink.report_status(ConnectionStatus {
+                        connected: false,
+                        message: format!(
+                            "{}. Retrying in {:.1}s (attempt {})...",
+                            message,
+                            delay.as_secs_f32(),
+                            backoff.get_attempt() + 1
+                        ),
+                        server_address: address.to_string(),
+                        attempt: backoff.get_attempt(),
+                        next_retry_at: Some(Self::get_unix_millis() + delay.as_millis() as u64),
+                        last_error_kind,
+                        ..Default::default()
+                    })
+                    .await;
+
+                    // Wait before retrying, unless the server address is changed in the meantime
+                    tokio::select! {
+                        _ = sleep(delay) => {}
+                        result = server_address.changed() => {
+                            // The source was removed, stop reconnecting
+                            if result.is_err() {
+                                break;
+                            }
+                            backoff.reset();
+                        }
+                    }
+                }
+                None => {
+                    sink.report_status(ConnectionStatus {
+                        connected: false,
+                        message: format!(
+                            "{}. Gave up after {} attempts, change the server address to try again",
+                            message,
+                            backoff.get_attempt()
+                        ),
+                        server_address: address.to_string(),
+                        attempt: backoff.get_attempt(),
+                        next_retry_at: None,
+                        last_error_kind,
+                        ..Default::default()
+                    })
+                    .await;
+
+                    // Only a new server address restarts the connection attempts
+                    if server_address.changed().await.is_err() {
+                        break;
+                    }
+                    backoff.reset();
+                }
+            }
+        }
+    }
+}
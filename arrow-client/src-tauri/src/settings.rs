@@ -0,0 +1,786 @@
+use crate::calibration::PostureBaseline;
+use crate::db_manager::DbManager;
+use crate::postures::Posture;
+use crate::tls;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transport {
+    #[default]
+    Tcp,
+    Unix,
+    WebSocket,
+    // Plays back a capture file recorded earlier instead of connecting to a server
+    Replay,
+}
+
+/*
+Where to reach the posture server: host and port for TCP,
+socket path for Unix sockets and a ws:// or wss:// URL for WebSocket.
+TCP connections use TLS when `tls` is set, WebSocket connections when the URL is wss://.
+*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerAddress {
+    pub transport: Transport,
+    pub host: String,
+    pub port: u16,
+    pub socket_path: String,
+    pub url: String,
+    pub tls: bool,
+    // SHA-256 fingerprint of the only server certificate to accept, e.g. a self-signed one
+    pub pinned_certificate: Option<String>,
+    // Pre-shared token sent to the server in the handshake
+    pub auth_token: Option<String>,
+    // Only used by the replay transport
+    pub capture_path: String,
+    // 1 replays at the recorded pace, 10 ten times faster
+    pub replay_speed: f32,
+    // Source whose lines are replayed from a multi-source capture, all lines if None
+    pub capture_source: Option<String>,
+}
+
+impl Default for ServerAddress {
+    fn default() -> Self {
+        Self {
+            transport: Transport::Tcp,
+            host: "127.0.0.1".to_string(),
+            port: 9876,
+            socket_path: String::new(),
+            url: String::new(),
+            tls: false,
+            pinned_certificate: None,
+            auth_token: None,
+            capture_path: String::new(),
+            replay_speed: 1.0,
+            capture_source: None,
+        }
+    }
+}
+
+impl fmt::Display for ServerAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.transport {
+            Transport::Tcp if self.tls => write!(f, "{}:{} (TLS)", self.host, self.port),
+            Transport::Tcp => write!(f, "{}:{}", self.host, self.port),
+            Transport::Unix => write!(f, "unix:{}", self.socket_path),
+            Transport::WebSocket => write!(f, "{}", self.url),
+            Transport::Replay => write!(f, "replay:{}", self.capture_path),
+        }
+    }
+}
+
+impl ServerAddress {
+    pub fn tcp(host: &str, port: u16) -> Result<Self, String> {
+        let address = Self {
+            transport: Transport::Tcp,
+            host: host.trim().to_string(),
+            port,
+            ..Default::default()
+        };
+        address.validate()?;
+
+        Ok(address)
+    }
+
+    pub fn unix(socket_path: &str) -> Result<Self, String> {
+        let address = Self {
+            transport: Transport::Unix,
+            socket_path: socket_path.trim().to_string(),
+            ..Default::default()
+        };
+        address.validate()?;
+
+        Ok(address)
+    }
+
+    pub fn websocket(url: &str) -> Result<Self, String> {
+        let address = Self {
+            transport: Transport::WebSocket,
+            url: url.trim().to_string(),
+            ..Default::default()
+        };
+        address.validate()?;
+
+        Ok(address)
+    }
+
+    pub fn replay(capture_path: &str, replay_speed: f32) -> Result<Self, String> {
+        let address = Self {
+            transport: Transport::Replay,
+            capture_path: capture_path.trim().to_string(),
+            replay_speed,
+            ..Default::default()
+        };
+        address.validate()?;
+
+        Ok(address)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match self.transport {
+            Transport::Tcp => {
+                if self.host.trim().is_empty() {
+                    return Err("Server host cannot be empty".to_string());
+                }
+                if self.port == 0 {
+                    return Err("Server port must be between 1 and 65535".to_string());
+                }
+            }
+            Transport::Unix => {
+                if self.tls {
+                    return Err("TLS is not supported over Unix sockets".to_string());
+                }
+                if cfg!(not(unix)) {
+                    return Err("Unix sockets are not supported on this platform".to_string());
+                }
+                if self.socket_path.trim().is_empty() {
+                    return Err("Server socket path cannot be empty".to_string());
+                }
+            }
+            Transport::WebSocket => {
+                let rest = if let Some(rest) = self.url.strip_prefix("wss://") {
+                    rest
+                } else if let Some(rest) = self.url.strip_prefix("ws://") {
+                    if self.tls {
+                        return Err("Use a wss:// URL for TLS WebSocket connections".to_string());
+                    }
+                    rest
+                } else {
+                    return Err("WebSocket URL must start with ws:// or wss://".to_string());
+                };
+                if rest.is_empty() || rest.starts_with('/') {
+                    return Err("WebSocket URL must include a host".to_string());
+                }
+            }
+            Transport::Replay => {
+                if self.capture_path.trim().is_empty() {
+                    return Err("Capture file path cannot be empty".to_string());
+                }
+                if !Path::new(&self.capture_path).is_file() {
+                    return Err(format!("Capture file not found: {}", self.capture_path));
+                }
+                if !(0.1..=1000.0).contains(&self.replay_speed) {
+                    return Err("Replay speed must be between 0.1 and 1000".to_string());
+                }
+            }
+        }
+
+        if let Some(fingerprint) = &self.pinned_certificate {
+            tls::normalize_fingerprint(fingerprint)?;
+        }
+        if self.auth_token.as_deref() == Some("") {
+            return Err("Authentication token cannot be empty".to_string());
+        }
+        if self.auth_token.is_some() && !self.is_confidential() {
+            return Err(
+                "Authentication tokens are only sent over TLS, wss:// or Unix sockets".to_string(),
+            );
+        }
+
+        Ok(())
+    }
+
+    // Whether the token can be sent without anyone on the network reading it
+    pub fn is_confidential(&self) -> bool {
+        match self.transport {
+            Transport::Unix => true,
+            Transport::WebSocket => self.url.starts_with("wss://"),
+            Transport::Tcp | Transport::Replay => self.tls,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReconnectPolicy {
+    pub initial_delay_ms: u64,
+    pub max_delay_ms: u64,
+    pub multiplier: f64,
+    // Fraction of the delay randomly added or removed, between 0 and 1
+    pub jitter: f64,
+    // Give up after this many consecutive failed attempts, retry forever if None
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay_ms: 1000,
+            max_delay_ms: 30000,
+            multiplier: 2.0,
+            jitter: 0.2,
+            max_attempts: None,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.initial_delay_ms < 100 {
+            return Err("Initial reconnect delay must be at least 100ms".to_string());
+        }
+        if self.max_delay_ms < self.initial_delay_ms {
+            return Err("Maximum reconnect delay must not be below the initial delay".to_string());
+        }
+        if !(1.0..=10.0).contains(&self.multiplier) {
+            return Err("Reconnect delay multiplier must be between 1 and 10".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.jitter) {
+            return Err("Reconnect jitter must be between 0 and 1".to_string());
+        }
+        if self.max_attempts == Some(0) {
+            return Err("Maximum reconnect attempts must be at least 1".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StreamSettings {
+    // Without a frame for this long, the posture switches to Stale
+    pub frame_timeout_ms: u64,
+    // Without a frame for this long, the connection is dropped and re-established
+    pub reconnect_timeout_ms: u64,
+}
+
+impl Default for StreamSettings {
+    fn default() -> Self {
+        Self {
+            frame_timeout_ms: 5000,
+            reconnect_timeout_ms: 30000,
+        }
+    }
+}
+
+impl StreamSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.frame_timeout_ms < 500 {
+            return Err("Frame timeout must be at least 500ms".to_string());
+        }
+        if self.reconnect_timeout_ms <= self.frame_timeout_ms {
+            return Err("Reconnect timeout must be longer than the frame timeout".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+/*
+Posture server started and supervised by the app, e.g. server/main.py.
+Off by default, the server is then expected to be started separately.
+The script is run as `<interpreter> <script_path> <args...>` with `env` added to the environment.
+*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerProcessSettings {
+    pub enabled: bool,
+    pub interpreter: String,
+    pub script_path: String,
+    pub args: Vec<String>,
+    pub env: BTreeMap<String, String>,
+    // Directory the server runs in, the directory of the script if None
+    pub working_dir: Option<String>,
+    // Delay between restarts when the server keeps crashing, gives up after `max_attempts`
+    pub restart: ReconnectPolicy,
+}
+
+impl Default for ServerProcessSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interpreter: "python".to_string(),
+            script_path: String::new(),
+            args: Vec::new(),
+            env: BTreeMap::new(),
+            working_dir: None,
+            restart: ReconnectPolicy::default(),
+        }
+    }
+}
+
+impl ServerProcessSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        self.restart
+            .validate()
+            .map_err(|e| format!("Server restart policy: {}", e))?;
+        if !self.enabled {
+            return Ok(());
+        }
+
+        if self.interpreter.trim().is_empty() {
+            return Err("Server interpreter cannot be empty".to_string());
+        }
+        if self.script_path.trim().is_empty() {
+            return Err("Server script path cannot be empty".to_string());
+        }
+        if self
+            .env
+            .keys()
+            .any(|key| key.is_empty() || key.contains('='))
+        {
+            return Err(
+                "Server environment variable names cannot be empty or contain '='".to_string(),
+            );
+        }
+
+        Ok(())
+    }
+}
+
+// Where the client listens for the beacons of the posture servers on the local network
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiscoverySettings {
+    // Off until the user opts in, listening binds a UDP port on every interface
+    pub enabled: bool,
+    pub port: u16,
+    // Joined to receive multicast beacons, broadcast and unicast beacons are received in any case
+    pub multicast_group: Option<Ipv4Addr>,
+    // Name of the server to connect to automatically whenever it is announced
+    pub auto_connect: Option<String>,
+    // Servers not announced for this long are forgotten
+    pub expiry_ms: u64,
+}
+
+impl Default for DiscoverySettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 9875,
+            multicast_group: Some(Ipv4Addr::new(239, 255, 98, 76)),
+            auto_connect: None,
+            expiry_ms: 10000,
+        }
+    }
+}
+
+impl DiscoverySettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.port == 0 {
+            return Err("Discovery port cannot be 0".to_string());
+        }
+        if self
+            .multicast_group
+            .is_some_and(|group| !group.is_multicast())
+        {
+            return Err("Discovery group must be a multicast address".to_string());
+        }
+        if self
+            .auto_connect
+            .as_ref()
+            .is_some_and(|name| name.trim().is_empty())
+        {
+            return Err("Auto-connect server name cannot be empty".to_string());
+        }
+        if self.expiry_ms < 1000 {
+            return Err("Discovery expiry must be at least 1000ms".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+/*
+Thresholds used to classify the posture from the landmarks.
+Depths are the MediaPipe z coordinates, smaller is closer to the camera,
+and slopes are the height difference between the left and right landmarks over their distance.
+*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PostureThresholds {
+    // Landmarks less visible than this count as missing
+    pub min_visibility: f32,
+    // Ears closer to the camera than the shoulders by more than this is slouching back
+    pub slouch_depth: f32,
+    // Ears closer than the shoulders by more than this is leaning in
+    pub lean_in_depth: f32,
+    // Shoulders closer to the camera than minus this rule out slouching back
+    pub slouch_shoulder_depth: f32,
+    // The same as the slouch and lean-in depths for a side camera, in image widths
+    pub side_slouch_gap: f32,
+    pub side_lean_in_gap: f32,
+    // Ear slope beyond which the head is tilted
+    pub head_tilt_slope: f32,
+    // Shoulder slope beyond which the body is tilted
+    pub body_tilt_slope: f32,
+}
+
+impl Default for PostureThresholds {
+    fn default() -> Self {
+        Self {
+            min_visibility: 0.9,
+            slouch_depth: 0.2,
+            lean_in_depth: 0.33,
+            slouch_shoulder_depth: 0.33,
+            side_slouch_gap: 0.05,
+            side_lean_in_gap: 0.1,
+            head_tilt_slope: 0.10,
+            body_tilt_slope: 0.10,
+        }
+    }
+}
+
+impl PostureThresholds {
+    /*
+    Thresholds for leaving `current`: the one that led to it is lowered by `hysteresis`,
+    so a measure hovering around it doesn't flip the posture back and forth.
+    */
+    pub fn with_hysteresis(&self, current: &Posture, hysteresis: f32) -> Self {
+        let exit = 1.0 - hysteresis;
+        let mut thresholds = self.clone();
+        match current {
+            Posture::SlouchingBack => {
+                thresholds.slouch_depth *= exit;
+                thresholds.side_slouch_gap *= exit;
+            }
+            Posture::LeaningIn => {
+                thresholds.lean_in_depth *= exit;
+                thresholds.side_lean_in_gap *= exit;
+            }
+            Posture::HeadTiltLeft | Posture::HeadTiltRight => thresholds.head_tilt_slope *= exit,
+            Posture::BodyTiltLeft | Posture::BodyTiltRight => thresholds.body_tilt_slope *= exit,
+            _ => {}
+        }
+        thresholds
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.min_visibility) {
+            return Err("Minimum visibility must be between 0 and 1".to_string());
+        }
+        if !(self.slouch_depth > 0.0 && self.slouch_depth <= 1.0) {
+            return Err("Slouch depth must be between 0 and 1".to_string());
+        }
+        if !(self.lean_in_depth > self.slouch_depth && self.lean_in_depth <= 2.0) {
+            return Err("Lean-in depth must be above the slouch depth and at most 2".to_string());
+        }
+        if !(self.slouch_shoulder_depth > 0.0 && self.slouch_shoulder_depth <= 2.0) {
+            return Err("Slouch shoulder depth must be between 0 and 2".to_string());
+        }
+        if !(self.side_slouch_gap > 0.0 && self.side_slouch_gap <= 1.0) {
+            return Err("Side slouch gap must be between 0 and 1".to_string());
+        }
+        if !(self.side_lean_in_gap > self.side_slouch_gap && self.side_lean_in_gap <= 1.0) {
+            return Err(
+                "Side lean-in gap must be above the side slouch gap and at most 1".to_string(),
+            );
+        }
+        if !(self.head_tilt_slope > 0.0 && self.head_tilt_slope <= 1.0) {
+            return Err("Head tilt slope must be between 0 and 1".to_string());
+        }
+        if !(self.body_tilt_slope > 0.0 && self.body_tilt_slope <= 1.0) {
+            return Err("Body tilt slope must be between 0 and 1".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+// Which classifier turns the measures into postures, see `classifier`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ClassifierSettings {
+    // The thresholds of the settings
+    #[default]
+    Rules,
+    // A linear model trained for the user, in a JSON file
+    Linear {
+        model_path: String,
+    },
+}
+
+// How long a new posture must last before it is logged and notified, see `smoothing`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SmoothingSettings {
+    pub min_dwell_ms: u64,
+    // Consecutive frames classified as the new posture
+    pub min_frames: u32,
+    // Fraction by which a measure must get back below the threshold to leave a posture
+    pub hysteresis: f32,
+}
+
+impl Default for SmoothingSettings {
+    fn default() -> Self {
+        Self {
+            min_dwell_ms: 1000,
+            min_frames: 3,
+            hysteresis: 0.2,
+        }
+    }
+}
+
+impl SmoothingSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.min_dwell_ms > 60000 {
+            return Err("Minimum dwell time must be at most 60000ms".to_string());
+        }
+        if !(1..=300).contains(&self.min_frames) {
+            return Err("Minimum frame count must be between 1 and 300".to_string());
+        }
+        if !(0.0..=0.9).contains(&self.hysteresis) {
+            return Err("Hysteresis must be between 0 and 0.9".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+// Name under which the `server` address shows up among the sources
+pub const PRIMARY_SOURCE: &str = "primary";
+// Name of the synthetic source fed by the demo mode
+pub const DEMO_SOURCE: &str = "demo";
+
+// Where a camera looks at the user from, its frames are measured accordingly, see `fusion`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CameraView {
+    #[default]
+    Front,
+    // On the left of the user
+    Left,
+    Right,
+}
+
+// An additional posture server fused with the primary one, e.g. a second camera on the side
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SourceConfig {
+    pub name: String,
+    pub address: ServerAddress,
+    // How much this source is trusted compared to the others, the primary source has a weight of 1
+    pub weight: f32,
+    // The primary source is a front camera
+    pub view: CameraView,
+}
+
+impl Default for SourceConfig {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            address: ServerAddress::default(),
+            weight: 1.0,
+            view: CameraView::Front,
+        }
+    }
+}
+
+impl SourceConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("Source name cannot be empty".to_string());
+        }
+        if self.name == PRIMARY_SOURCE || self.name == DEMO_SOURCE {
+            return Err(format!("Source name '{}' is reserved", self.name));
+        }
+        if !(self.weight > 0.0 && self.weight <= 10.0) {
+            return Err("Source weight must be between 0 and 10".to_string());
+        }
+
+        self.address
+            .validate()
+            .map_err(|e| format!("Source '{}': {}", self.name, e))
+    }
+
+    pub fn validate_all(sources: &[SourceConfig]) -> Result<(), String> {
+        for (index, source) in sources.iter().enumerate() {
+            source.validate()?;
+            if sources[..index]
+                .iter()
+                .any(|other| other.name == source.name)
+            {
+                return Err(format!("Duplicate source name '{}'", source.name));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Timeline of postures played by the demo mode
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DemoScenario {
+    // Goes through every posture, sitting straight in between, holding each for `hold_secs`
+    Cycle { hold_secs: u64 },
+    // Sits straight, then slowly slouches after `straight_minutes`
+    SlouchAfter { straight_minutes: u64 },
+    // Sits straight with a short head tilt every `interval_secs`, alternating sides
+    FrequentHeadTilts { interval_secs: u64 },
+    // Holds a single posture
+    Steady { posture: Posture },
+}
+
+/*
+Synthetic landmarks generated by the backend itself, to work on the app without a camera.
+`speed` compresses the scenario timeline, e.g. 60 plays a minute every second.
+*/
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DemoSettings {
+    pub enabled: bool,
+    pub scenario: DemoScenario,
+    // Amplitude of the random jitter added to every coordinate
+    pub noise: f32,
+    // Average number of times per minute the ears or shoulders briefly go out of view
+    pub dropouts_per_minute: f32,
+    pub frame_rate: f32,
+    pub speed: f32,
+}
+
+impl Default for DemoSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            scenario: DemoScenario::Cycle { hold_secs: 10 },
+            noise: 0.004,
+            dropouts_per_minute: 1.0,
+            frame_rate: 10.0,
+            speed: 1.0,
+        }
+    }
+}
+
+impl DemoSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        match &self.scenario {
+            DemoScenario::Cycle { hold_secs } if *hold_secs == 0 => {
+                return Err("Demo posture hold time must be at least 1s".to_string());
+            }
+            DemoScenario::FrequentHeadTilts { interval_secs } if *interval_secs < 5 => {
+                return Err("Demo head tilt interval must be at least 5s".to_string());
+            }
+            DemoScenario::Steady {
+                posture: posture @ (Posture::Unknown | Posture::Paused),
+            } => {
+                return Err(format!("Demo posture cannot be {:?}", posture));
+            }
+            _ => {}
+        }
+        if !(0.0..=0.05).contains(&self.noise) {
+            return Err("Demo noise must be between 0 and 0.05".to_string());
+        }
+        if !(0.0..=60.0).contains(&self.dropouts_per_minute) {
+            return Err("Demo dropouts must be between 0 and 60 per minute".to_string());
+        }
+        if !(1.0..=60.0).contains(&self.frame_rate) {
+            return Err("Demo frame rate must be between 1 and 60 fps".to_string());
+        }
+        if !(0.1..=1000.0).contains(&self.speed) {
+            return Err("Demo speed must be between 0.1 and 1000".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub server: ServerAddress,
+    // Additional sources whose landmarks are merged with the ones from `server`
+    pub sources: Vec<SourceConfig>,
+    pub reconnect: ReconnectPolicy,
+    pub stream: StreamSettings,
+    pub thresholds: PostureThresholds,
+    // Neutral posture of the user, the thresholds apply to the deviations from it
+    pub baseline: Option<PostureBaseline>,
+    pub smoothing: SmoothingSettings,
+    pub classifier: ClassifierSettings,
+    pub demo: DemoSettings,
+    pub server_process: ServerProcessSettings,
+    pub discovery: DiscoverySettings,
+}
+
+impl Settings {
+    /*
+    Loads the settings file from the app data directory.
+    Falls back to the defaults if the file is missing or cannot be parsed,
+    so a corrupted settings file never prevents the app from starting.
+    */
+    pub fn load() -> Self {
+        let path = Settings::get_settings_path();
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => match serde_json::from_str::<Settings>(&contents) {
+                Ok(settings) => settings.validated(),
+                Err(e) => {
+                    eprintln!("Failed to parse settings file, using defaults: {}", e);
+                    Settings::default()
+                }
+            },
+            Err(_) => Settings::default(),
+        }
+    }
+
+    /*
+    Sections out of range, e.g. edited by hand or saved by an older version, fall back to
+    their defaults, the commands that change them validate them the same way.
+    */
+    pub fn validated(mut self) -> Self {
+        or_default(&mut self.server, "server", ServerAddress::validate);
+        or_default(&mut self.sources, "sources", |sources| {
+            SourceConfig::validate_all(sources)
+        });
+        or_default(&mut self.reconnect, "reconnect", ReconnectPolicy::validate);
+        or_default(&mut self.stream, "stream", StreamSettings::validate);
+        or_default(
+            &mut self.thresholds,
+            "thresholds",
+            PostureThresholds::validate,
+        );
+        or_default(
+            &mut self.smoothing,
+            "smoothing",
+            SmoothingSettings::validate,
+        );
+        or_default(&mut self.demo, "demo", DemoSettings::validate);
+        or_default(
+            &mut self.server_process,
+            "server_process",
+            ServerProcessSettings::validate,
+        );
+        or_default(
+            &mut self.discovery,
+            "discovery",
+            DiscoverySettings::validate,
+        );
+        self
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let path = Settings::get_settings_path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+
+        Ok(())
+    }
+
+    fn get_settings_path() -> PathBuf {
+        DbManager::get_app_data_dir().join("settings.json")
+    }
+}
+
+fn or_default<T: Default>(
+    section: &mut T,
+    name: &str,
+    validate: impl Fn(&T) -> Result<(), String>,
+) {
+    if let Err(e) = validate(section) {
+        eprintln!("Invalid {} in settings file, using defaults: {}", name, e);
+        *section = T::default();
+    }
+}
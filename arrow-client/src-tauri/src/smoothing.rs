@@ -0,0 +1,68 @@
+use crate::postures::Posture;
+use crate::settings::SmoothingSettings;
+use tokio::time::{Duration, Instant};
+
+/*
+Decides when the classified posture replaces the current one.
+A new posture is only accepted once it was classified for `min_frames` frames in a row
+spanning at least `min_dwell_ms`, so a borderline pose doesn't log and notify on every frame.
+The hysteresis is applied before, by the classifier given the current posture,
+see `PostureClassifier`.
+*/
+#[derive(Debug, Default)]
+pub struct PostureSmoother {
+    candidate: Option<Candidate>,
+}
+
+#[derive(Debug)]
+struct Candidate {
+    posture: Posture,
+    since: Instant,
+    frames: u32,
+}
+
+impl PostureSmoother {
+    // Returns true if `classified` replaces `current`
+    pub fn accept(
+        &mut self,
+        current: &Posture,
+        classified: &Posture,
+        now: Instant,
+        settings: &SmoothingSettings,
+    ) -> bool {
+        if classified.get_posture_value() == current.get_posture_value() {
+            self.candidate = None;
+            return false;
+        }
+
+        // Nothing to smooth when there was no posture, e.g. on the first frame or after a pause
+        if matches!(current, Posture::Unknown | Posture::Stale | Posture::Paused) {
+            self.candidate = None;
+            return true;
+        }
+
+        let candidate = match self.candidate.take() {
+            Some(mut candidate)
+                if candidate.posture.get_posture_value() == classified.get_posture_value() =>
+            {
+                candidate.frames += 1;
+                candidate
+            }
+            _ => Candidate {
+                posture: classified.clone(),
+                since: now,
+                frames: 1,
+            },
+        };
+
+        let dwell = now.duration_since(candidate.since);
+        if candidate.frames >= settings.min_frames
+            && dwell >= Duration::from_millis(settings.min_dwell_ms)
+        {
+            return true;
+        }
+
+        self.candidate = Some(candidate);
+        false
+    }
+}
@@ -0,0 +1,81 @@
+use crate::events::{ConnectionStatus, StreamDiagnostics};
+use crate::landmarks::PoseLandmarks;
+use crate::pipeline::Pipeline;
+use crate::protocol::{FrameMetadata, ServerInfo};
+use crate::settings::CameraView;
+use std::future::Future;
+use tokio::time::Duration;
+
+/*
+Anything that produces pose frames: posture servers over any transport, capture replays,
+synthetic generators... A source only reports what it sees to its sink.
+Fusion, classification, logging and notifications are done once for all sources by the pipeline.
+*/
+pub trait PostureSource: Send + 'static {
+    fn get_name(&self) -> &str;
+
+    // How much the frames of this source are trusted when fused with the other sources
+    fn get_weight(&self) -> f32 {
+        1.0
+    }
+
+    // How the frames of this source are measured, see `fusion`
+    fn get_view(&self) -> CameraView {
+        CameraView::Front
+    }
+
+    // Feeds the sink until the source is exhausted, the task running it may be aborted at any time
+    fn run(self, sink: FrameSink) -> impl Future<Output = ()> + Send;
+}
+
+// Handle through which a running source feeds the pipeline
+#[derive(Clone)]
+pub struct FrameSink {
+    source: String,
+    weight: f32,
+    view: CameraView,
+    pipeline: Pipeline,
+}
+
+impl FrameSink {
+    pub fn new(source: &str, weight: f32, view: CameraView, pipeline: Pipeline) -> Self {
+        Self {
+            source: source.to_string(),
+            weight,
+            view,
+            pipeline,
+        }
+    }
+
+    pub fn get_source_name(&self) -> &str {
+        &self.source
+    }
+
+    pub async fn report_status(&self, status: ConnectionStatus) {
+        self.pipeline.report_status(&self.source, status).await;
+    }
+
+    pub fn report_diagnostics(&self, diagnostics: StreamDiagnostics) {
+        self.pipeline.report_diagnostics(diagnostics);
+    }
+
+    pub async fn set_server_info(&self, info: Option<ServerInfo>) {
+        self.pipeline.set_server_info(&self.source, info).await;
+    }
+
+    pub async fn push_frame(&self, landmarks: PoseLandmarks, metadata: FrameMetadata) {
+        self.pipeline
+            .push_frame(&self.source, self.weight, self.view, landmarks, metadata)
+            .await;
+    }
+
+    // Connected, but no frame for `silence`
+    pub async fn mark_stale(&self, silence: Duration) {
+        self.pipeline.mark_source_stale(&self.source, silence).await;
+    }
+
+    // Frames received so far no longer describe the current pose
+    pub async fn disconnected(&self) {
+        self.pipeline.source_disconnected(&self.source).await;
+    }
+}
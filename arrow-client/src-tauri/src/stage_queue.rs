@@ -0,0 +1,67 @@
+use crate::events::QueueStats;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::mpsc::{self, error::TrySendError};
+
+/*
+Bounded queue between two stages of the pipeline.
+Pushing never waits: when the consumer falls behind and the queue is full, the item is dropped
+and counted, so a slow disk or notification daemon can never stall the socket reads.
+Items that must not be lost are sent instead, waiting for room.
+Each stage decides what a dropped item means, see the queues of the pipeline.
+*/
+pub struct StageQueue<T> {
+    name: &'static str,
+    sender: mpsc::Sender<T>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl<T> Clone for StageQueue<T> {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name,
+            sender: self.sender.clone(),
+            dropped: self.dropped.clone(),
+        }
+    }
+}
+
+impl<T> StageQueue<T> {
+    pub fn new(name: &'static str, capacity: usize) -> (Self, mpsc::Receiver<T>) {
+        let (sender, receiver) = mpsc::channel(capacity);
+        let queue = Self {
+            name,
+            sender,
+            dropped: Arc::new(AtomicU64::new(0)),
+        };
+        (queue, receiver)
+    }
+
+    // Returns false if the item was dropped
+    pub fn push(&self, item: T) -> bool {
+        match self.sender.try_send(item) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                false
+            }
+            // The consumer task is gone, e.g. the runtime is shutting down
+            Err(TrySendError::Closed(_)) => false,
+        }
+    }
+
+    // Waits for room in the queue, for items that must not be lost
+    pub async fn send(&self, item: T) {
+        let _ = self.sender.send(item).await;
+    }
+
+    pub fn get_stats(&self) -> QueueStats {
+        let capacity = self.sender.max_capacity();
+        QueueStats {
+            name: self.name.to_string(),
+            depth: capacity - self.sender.capacity(),
+            capacity,
+            dropped: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
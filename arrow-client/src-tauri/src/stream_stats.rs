@@ -0,0 +1,105 @@
+use crate::events::StreamStats;
+use crate::protocol::FrameMetadata;
+use std::collections::VecDeque;
+use tokio::time::{Duration, Instant};
+
+// The frame rate is measured over the frames received within this window
+const RATE_WINDOW: Duration = Duration::from_secs(5);
+// The latency is measured over this many frames
+const LATENCY_SAMPLES: usize = 100;
+
+/*
+Frame statistics of one source.
+Sequence numbers reveal the frames dropped or reordered between the camera and the client,
+capture timestamps the time from the camera to the posture update.
+Both are optional, sources that don't send them only get a frame rate.
+*/
+#[derive(Debug, Default)]
+pub struct StreamStatsTracker {
+    frames: u64,
+    // Arrival times of the frames within the rate window
+    arrivals: VecDeque<Instant>,
+    last_seq: Option<u64>,
+    dropped_frames: u64,
+    gaps: u64,
+    out_of_order_frames: u64,
+    latencies_ms: VecDeque<f64>,
+}
+
+impl StreamStatsTracker {
+    pub fn record_frame(&mut self, metadata: &FrameMetadata, now: Instant) {
+        self.frames += 1;
+        self.arrivals.push_back(now);
+        while self
+            .arrivals
+            .front()
+            .is_some_and(|arrival| now.duration_since(*arrival) >= RATE_WINDOW)
+        {
+            self.arrivals.pop_front();
+        }
+
+        let Some(seq) = metadata.seq else {
+            return;
+        };
+        match self.last_seq {
+            Some(last_seq) if seq <= last_seq => {
+                // Late or repeated, the sequence keeps going from the latest frame
+                self.out_of_order_frames += 1;
+                return;
+            }
+            Some(last_seq) if seq > last_seq + 1 => {
+                self.gaps += 1;
+                self.dropped_frames += seq - last_seq - 1;
+            }
+            _ => {}
+        }
+        self.last_seq = Some(seq);
+    }
+
+    pub fn record_latency(&mut self, latency_ms: f64) {
+        if self.latencies_ms.len() == LATENCY_SAMPLES {
+            self.latencies_ms.pop_front();
+        }
+        self.latencies_ms.push_back(latency_ms);
+    }
+
+    // Servers number their frames from the start again on every connection
+    pub fn reset_sequence(&mut self) {
+        self.last_seq = None;
+    }
+
+    pub fn get_stats(&self, source: &str, now: Instant) -> StreamStats {
+        let recent: Vec<&Instant> = self
+            .arrivals
+            .iter()
+            .filter(|arrival| now.duration_since(**arrival) < RATE_WINDOW)
+            .collect();
+        let frames_per_second = match (recent.first(), recent.last()) {
+            (Some(first), Some(last)) if last > first => {
+                (recent.len() - 1) as f32 / last.duration_since(**first).as_secs_f32()
+            }
+            _ => 0.0,
+        };
+
+        let latency_ms = (!self.latencies_ms.is_empty()).then(|| {
+            (self.latencies_ms.iter().sum::<f64>() / self.latencies_ms.len() as f64) as f32
+        });
+        let max_latency_ms = self
+            .latencies_ms
+            .iter()
+            .copied()
+            .reduce(f64::max)
+            .map(|latency| latency as f32);
+
+        StreamStats {
+            source: source.to_string(),
+            frames: self.frames,
+            frames_per_second,
+            dropped_frames: self.dropped_frames,
+            gaps: self.gaps,
+            out_of_order_frames: self.out_of_order_frames,
+            latency_ms,
+            max_latency_ms,
+        }
+    }
+}
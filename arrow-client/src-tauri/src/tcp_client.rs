@@ -1,287 +1,401 @@
+use crate::capture::{CaptureRecording, CaptureWriter};
+use crate::classifier::PostureClassifier;
 use crate::db_manager::DbManager;
+use crate::demo::DemoSource;
 use crate::events::{
-    ConnectionStatus, NotificationEvent, PostureMetrics, PostureUpdate, SessionLogsUpdate,
+    ConnectionStatus, MonitoringState, QueueStats, StreamDiagnostics, StreamStats,
 };
-use crate::notification_service::NotificationService;
-use crate::postures::Posture;
+use crate::pipeline::Pipeline;
+use crate::protocol::{ControlCommand, ServerInfo};
+use crate::server_source::{CommandReply, CommandRequest, ServerSource, ServerSourceContext};
+use crate::settings::{
+    CameraView, DemoSettings, ServerAddress, Settings, SourceConfig, DEMO_SOURCE, PRIMARY_SOURCE,
+};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 use std::sync::Arc;
-use tauri::{AppHandle, Emitter};
-use tokio::io::{AsyncBufReadExt, BufReader};
-use tokio::net::TcpStream;
-use tokio::sync::Mutex;
-use tokio::time::{sleep, Duration};
-
+use tauri::AppHandle;
+use tokio::sync::{mpsc, watch, Mutex};
+use tokio::task::JoinHandle;
+use tokio::time::Duration;
+
+/*
+Runs the posture sources configured in the settings and feeds them to the pipeline:
+the primary server, whose address can be changed while connected, the additional sources
+and the demo source when the demo mode is on.
+Monitoring is started, paused, resumed and stopped from here, no source runs while stopped.
+Control commands are forwarded to the server of the source they are meant for.
+*/
 pub struct TcpClient {
-    app_handle: AppHandle,
-    connection_status: Arc<Mutex<bool>>,
-    db_manager: Arc<Mutex<Option<DbManager>>>,
-    notification_service: Arc<NotificationService>,
-    current_posture: Arc<Mutex<Posture>>,
+    server_address: watch::Sender<ServerAddress>,
+    // While true, the servers are asked to pause and release their camera
+    paused: watch::Sender<bool>,
+    // Held for the whole of every lifecycle transition
+    state: Mutex<MonitoringState>,
+    primary_task: Mutex<Option<SourceTask>>,
+    source_tasks: Mutex<Vec<SourceTask>>,
+    demo_task: Mutex<Option<JoinHandle<()>>>,
+    pipeline: Pipeline,
+    context: ServerSourceContext,
 }
 
+// Task of a server source, replaced whenever the sources are changed
+struct SourceTask {
+    name: String,
+    // Kept alive for an additional source, which stops once its address sender is dropped.
+    // None for the primary source, which follows the address of the client.
+    _address: Option<watch::Sender<ServerAddress>>,
+    commands: mpsc::Sender<CommandRequest>,
+    handle: JoinHandle<()>,
+}
+
+// Commands waiting to be sent, only the latest ones matter
+const COMMAND_QUEUE_SIZE: usize = 8;
+
 impl TcpClient {
-    pub fn new(app_handle: AppHandle, db_manager: Arc<Mutex<Option<DbManager>>>) -> Self {
+    pub fn new(
+        app_handle: AppHandle,
+        db_manager: Arc<Mutex<Option<DbManager>>>,
+        server_address: ServerAddress,
+        settings: Arc<Mutex<Settings>>,
+    ) -> Self {
+        let (server_address, _) = watch::channel(server_address);
+        let (paused, _) = watch::channel(false);
+
         Self {
-            app_handle,
-            connection_status: Arc::new(Mutex::new(false)),
-            db_manager,
-            notification_service: Arc::new(NotificationService::new()),
-            current_posture: Arc::new(Mutex::new(Posture::Unknown)),
+            server_address,
+            state: Mutex::new(MonitoringState::Stopped),
+            primary_task: Mutex::new(None),
+            source_tasks: Mutex::new(Vec::new()),
+            demo_task: Mutex::new(None),
+            pipeline: Pipeline::new(app_handle, db_manager, settings.clone()),
+            context: ServerSourceContext {
+                diagnostics: Arc::new(Mutex::new(BTreeMap::new())),
+                recorder: Arc::new(Mutex::new(None)),
+                settings,
+                paused: paused.subscribe(),
+            },
+            paused,
         }
     }
 
     pub async fn initialize_notifications(&self) -> Result<(), String> {
-        self.notification_service.initialize().await
+        self.pipeline.initialize_notifications().await
     }
 
-    pub async fn start(&self) {
-        let app_handle = self.app_handle.clone();
-        let connection_status = self.connection_status.clone();
-        let db_manager = self.db_manager.clone();
-        let notification_service = self.notification_service.clone();
-        let current_posture = self.current_posture.clone();
-
-        tokio::spawn(async move {
-            loop {
-                match TcpStream::connect("127.0.0.1:9876").await {
-                    Ok(stream) => {
-                        {
-                            let mut status = connection_status.lock().await;
-                            *status = true;
-                        }
-
-                        let _ = app_handle.emit(
-                            "connection-status",
-                            ConnectionStatus {
-                                connected: true,
-                                message: "Connected to posture server".to_string(),
-                            },
-                        );
-
-                        if let Err(e) = Self::handle_connection(
-                            stream,
-                            &app_handle,
-                            &db_manager,
-                            &notification_service,
-                            &current_posture,
-                        )
-                        .await
-                        {
-                            eprintln!("Connection error: {}", e);
-                        }
-
-                        {
-                            let mut status = connection_status.lock().await;
-                            *status = false;
-                        }
-
-                        let _ = app_handle.emit(
-                            "connection-status",
-                            ConnectionStatus {
-                                connected: false,
-                                message: "Disconnected from server. Retrying...".to_string(),
-                            },
-                        );
-                    }
-                    Err(e) => {
-                        let _ = app_handle.emit(
-                            "connection-status",
-                            ConnectionStatus {
-                                connected: false,
-                                message: format!("Connection failed: {}. Retrying...", e),
-                            },
-                        );
-                    }
-                }
-
-                // Wait before retrying
-                sleep(Duration::from_secs(3)).await;
-            }
-        });
+    pub async fn start(&self) -> Result<(), String> {
+        let mut state = self.state.lock().await;
+        if *state != MonitoringState::Stopped {
+            return Err("Monitoring is already started".to_string());
+        }
+
+        self.pipeline.start().await;
+        self.pipeline.set_paused(false).await;
+
+        *self.primary_task.lock().await = Some(self.spawn_server_source(
+            PRIMARY_SOURCE,
+            1.0,
+            CameraView::Front,
+            None,
+            self.server_address.subscribe(),
+        ));
+
+        let (sources, demo) = {
+            let settings = self.context.settings.lock().await;
+            (settings.sources.clone(), settings.demo.clone())
+        };
+        self.replace_sources(sources, true).await;
+        self.replace_demo(demo, true).await;
+
+        *state = MonitoringState::Running;
+        self.pipeline.report_monitoring_state(*state);
+        Ok(())
     }
 
-    async fn handle_connection(
-        stream: TcpStream,
-        app_handle: &AppHandle,
-        db_manager: &Arc<Mutex<Option<DbManager>>>,
-        notification_service: &Arc<NotificationService>,
-        current_posture: &Arc<Mutex<Posture>>,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let mut reader = BufReader::new(stream);
-        let mut line = String::new();
-
-        loop {
-            line.clear();
-            match reader.read_line(&mut line).await {
-                Ok(0) => {
-                    // EOF - server closed connection
-                    break;
-                }
-                Ok(_) => {
-                    // Remove trailing newline
-                    if line.ends_with('\n') {
-                        line.pop();
-                        if line.ends_with('\r') {
-                            line.pop();
-                        }
-                    }
-
-                    if let Some(posture_update) = Self::parse_metrics(&line) {
-                        // Check for posture change and handle logging/notifications
-                        let previous_posture = {
-                            let mut current = current_posture.lock().await;
-                            let previous = current.clone();
-                            *current = posture_update.posture.clone();
-                            previous
-                        };
-
-                        let posture_changed = posture_update.posture.get_posture_value()
-                            != previous_posture.get_posture_value();
-
-                        if posture_changed {
-                            // Log posture change to database
-                            if let Some(db) = db_manager.lock().await.as_ref() {
-                                let _ = db.log_posture_change(
-                                    &posture_update.posture.get_posture_value(),
-                                    &previous_posture.get_posture_value(),
-                                );
-                            }
-
-                            // Send notification
-                            let is_good_posture =
-                                posture_update.posture.get_posture_value() == "STRAIGHT";
-                            notification_service
-                                .notify_posture_change(&posture_update.posture, is_good_posture)
-                                .await;
-
-                            // Emit session logs update event
-                            if let Some(db) = db_manager.lock().await.as_ref() {
-                                if let Ok(Some(logs)) = db.get_session_logs() {
-                                    let _ = app_handle
-                                        .emit("session-logs-updated", SessionLogsUpdate { logs });
-                                }
-                            }
-
-                            // Emit notification event
-                            let _ = app_handle.emit(
-                                "notification-triggered",
-                                NotificationEvent {
-                                    posture: posture_update.posture.get_posture_value(),
-                                    message: posture_update.posture.get_posture_message(),
-                                    is_good_posture,
-                                },
-                            );
-                        }
-
-                        // Always emit posture update
-                        let _ = app_handle.emit("posture-update", posture_update);
-                    }
-                }
-                Err(e) => {
-                    return Err(Box::new(e));
-                }
-            }
+    // Disconnects every source, the time until monitoring starts again is logged as paused
+    pub async fn stop(&self) {
+        let mut state = self.state.lock().await;
+        if *state == MonitoringState::Stopped {
+            return;
+        }
+
+        if let Some(task) = self.primary_task.lock().await.take() {
+            self.stop_server_source(task).await;
+        }
+        self.replace_sources(Vec::new(), false).await;
+        self.replace_demo(DemoSettings::default(), false).await;
+
+        self.set_servers_paused(false);
+        self.pipeline.set_paused(true).await;
+
+        *state = MonitoringState::Stopped;
+        self.pipeline.report_monitoring_state(*state);
+    }
+
+    // Stays connected, but the servers release their camera and the frames are ignored
+    pub async fn pause(&self) -> Result<(), String> {
+        let mut state = self.state.lock().await;
+        if *state != MonitoringState::Running {
+            return Err("Monitoring is not running".to_string());
         }
 
+        self.set_servers_paused(true);
+        self.pipeline.set_paused(true).await;
+
+        *state = MonitoringState::Paused;
+        self.pipeline.report_monitoring_state(*state);
         Ok(())
     }
 
-    fn parse_metrics(metrics_str: &str) -> Option<PostureUpdate> {
-        let parts: Vec<&str> = metrics_str.split('|').collect();
-        if parts.len() == 16 {
-            let metrics = PostureMetrics {
-                left_ear: crate::events::Point3D {
-                    x: parts[0].parse::<f32>().unwrap_or(0.0),
-                    y: parts[1].parse::<f32>().unwrap_or(0.0),
-                    z: parts[2].parse::<f32>().unwrap_or(0.0),
-                    visibility: parts[3].parse::<f32>().unwrap_or(0.0),
-                },
-                right_ear: crate::events::Point3D {
-                    x: parts[4].parse::<f32>().unwrap_or(0.0),
-                    y: parts[5].parse::<f32>().unwrap_or(0.0),
-                    z: parts[6].parse::<f32>().unwrap_or(0.0),
-                    visibility: parts[7].parse::<f32>().unwrap_or(0.0),
-                },
-                left_shoulder: crate::events::Point3D {
-                    x: parts[8].parse::<f32>().unwrap_or(0.0),
-                    y: parts[9].parse::<f32>().unwrap_or(0.0),
-                    z: parts[10].parse::<f32>().unwrap_or(0.0),
-                    visibility: parts[11].parse::<f32>().unwrap_or(0.0),
-                },
-                right_shoulder: crate::events::Point3D {
-                    x: parts[12].parse::<f32>().unwrap_or(0.0),
-                    y: parts[13].parse::<f32>().unwrap_or(0.0),
-                    z: parts[14].parse::<f32>().unwrap_or(0.0),
-                    visibility: parts[15].parse::<f32>().unwrap_or(0.0),
-                },
-            };
-
-            let posture = Self::determine_posture(&metrics);
-            let message = posture.get_posture_message();
-
-            Some(PostureUpdate {
-                posture,
-                message,
-                metrics: Some(metrics),
-            })
-        } else {
-            None
+    pub async fn resume(&self) -> Result<(), String> {
+        let mut state = self.state.lock().await;
+        if *state != MonitoringState::Paused {
+            return Err("Monitoring is not paused".to_string());
         }
+
+        self.set_servers_paused(false);
+        self.pipeline.set_paused(false).await;
+
+        *state = MonitoringState::Running;
+        self.pipeline.report_monitoring_state(*state);
+        Ok(())
+    }
+
+    pub async fn set_classifier(&self, classifier: Arc<dyn PostureClassifier>) {
+        self.pipeline.set_classifier(classifier).await;
     }
 
-    fn determine_posture(metrics: &PostureMetrics) -> Posture {
-        let PostureMetrics {
-            left_ear,
-            right_ear,
-            left_shoulder,
-            right_shoulder,
-        } = metrics;
-
-        // Check visibility
-        if left_shoulder.visibility < 0.9 || right_shoulder.visibility < 0.9 {
-            return Posture::ShouldersNotVisible;
+    // Only while running, the frames are ignored while paused
+    pub async fn start_calibration(&self, duration: Duration) -> Result<(), String> {
+        if *self.state.lock().await != MonitoringState::Running {
+            return Err("Monitoring is not running".to_string());
         }
 
-        if left_ear.visibility < 0.9 || right_ear.visibility < 0.9 {
-            return Posture::HeadNotVisible;
+        self.pipeline.start_calibration(duration).await
+    }
+
+    pub async fn get_state(&self) -> MonitoringState {
+        *self.state.lock().await
+    }
+
+    // Restarts the demo source with the new settings, or stops it if the demo mode is off
+    pub async fn set_demo(&self, demo: DemoSettings) {
+        let state = self.state.lock().await;
+        self.replace_demo(demo, *state != MonitoringState::Stopped)
+            .await;
+    }
+
+    // Replaces the additional sources, the primary source keeps its connection
+    pub async fn set_sources(&self, sources: Vec<SourceConfig>) {
+        let state = self.state.lock().await;
+        self.replace_sources(sources, *state != MonitoringState::Stopped)
+            .await;
+    }
+
+    async fn replace_demo(&self, demo: DemoSettings, running: bool) {
+        let mut demo_task = self.demo_task.lock().await;
+
+        if let Some(handle) = demo_task.take() {
+            handle.abort();
+            let _ = handle.await;
+            self.pipeline.remove_source(DEMO_SOURCE).await;
         }
 
-        // Calculate avg depths
-        let avg_ear_depth = (left_ear.z + right_ear.z) / 2.0;
-        let avg_shoulder_depth = (left_shoulder.z + right_shoulder.z) / 2.0;
+        if running && demo.enabled {
+            let source = DemoSource::new(demo, self.context.settings.clone());
+            *demo_task = Some(self.pipeline.spawn_source(source));
+        }
+    }
+
+    async fn replace_sources(&self, sources: Vec<SourceConfig>, running: bool) {
+        let mut source_tasks = self.source_tasks.lock().await;
 
-        // Check slouching
-        if avg_ear_depth + 0.2 < avg_shoulder_depth && avg_shoulder_depth > -0.33 {
-            return Posture::SlouchingBack;
+        for task in source_tasks.drain(..) {
+            self.stop_server_source(task).await;
         }
-        if avg_ear_depth + 0.33 < avg_shoulder_depth {
-            return Posture::LeaningIn;
+        if !running {
+            return;
         }
 
-        // Calculate ear slope for head tilt
-        let ear_slope = (left_ear.y - right_ear.y) / (left_ear.x - right_ear.x);
-        if ear_slope > 0.10 {
-            return Posture::HeadTiltRight;
+        for config in sources {
+            let (address, receiver) = watch::channel(config.address);
+            source_tasks.push(self.spawn_server_source(
+                &config.name,
+                config.weight,
+                config.view,
+                Some(address),
+                receiver,
+            ));
         }
-        if ear_slope < -0.10 {
-            return Posture::HeadTiltLeft;
+    }
+
+    fn spawn_server_source(
+        &self,
+        name: &str,
+        weight: f32,
+        view: CameraView,
+        address: Option<watch::Sender<ServerAddress>>,
+        receiver: watch::Receiver<ServerAddress>,
+    ) -> SourceTask {
+        let (commands, command_receiver) = mpsc::channel(COMMAND_QUEUE_SIZE);
+        let handle = self.pipeline.spawn_source(ServerSource::new(
+            name,
+            weight,
+            view,
+            receiver,
+            command_receiver,
+            self.context.clone(),
+        ));
+
+        SourceTask {
+            name: name.to_string(),
+            _address: address,
+            commands,
+            handle,
         }
+    }
+
+    async fn stop_server_source(&self, task: SourceTask) {
+        task.handle.abort();
+        let _ = task.handle.await;
+        self.pipeline.remove_source(&task.name).await;
+        self.context.diagnostics.lock().await.remove(&task.name);
+    }
+
+    fn set_servers_paused(&self, paused: bool) {
+        self.paused.send_if_modified(|current| {
+            let modified = *current != paused;
+            *current = paused;
+            modified
+        });
+    }
+
+    pub fn set_server_address(&self, address: ServerAddress) {
+        self.server_address.send_if_modified(|current| {
+            if *current == address {
+                false
+            } else {
+                *current = address;
+                true
+            }
+        });
+    }
 
-        // Calculate shoulder slope for body tilt
-        let shoulder_slope =
-            (left_shoulder.y - right_shoulder.y) / (left_shoulder.x - right_shoulder.x);
-        if shoulder_slope > 0.10 {
-            return Posture::BodyTiltRight;
+    /*
+    Queues a command for the server of a connected source.
+    The returned reply is only awaited with `server_source::wait_for_ack`,
+    so the client doesn't have to stay locked until the server answers.
+    */
+    pub async fn send_command(
+        &self,
+        source: &str,
+        command: ControlCommand,
+    ) -> Result<CommandReply, String> {
+        let commands = if source == PRIMARY_SOURCE {
+            let primary_task = self.primary_task.lock().await;
+            let task = primary_task
+                .as_ref()
+                .ok_or_else(|| "Monitoring is stopped".to_string())?;
+            task.commands.clone()
+        } else {
+            let source_tasks = self.source_tasks.lock().await;
+            let task = source_tasks
+                .iter()
+                .find(|task| task.name == source)
+                .ok_or_else(|| format!("Unknown source: {}", source))?;
+            task.commands.clone()
+        };
+
+        let connected = self
+            .pipeline
+            .get_sources()
+            .await
+            .iter()
+            .any(|status| status.name == source && status.connected);
+        if !connected {
+            return Err(format!("Source {} is not connected", source));
         }
-        if shoulder_slope < -0.10 {
-            return Posture::BodyTiltLeft;
+
+        let (request, reply) = CommandRequest::new(command);
+        commands
+            .try_send(request)
+            .map_err(|_| format!("Too many commands pending for {}", source))?;
+
+        Ok(reply)
+    }
+
+    pub fn get_server_address(&self) -> ServerAddress {
+        self.server_address.borrow().clone()
+    }
+
+    pub async fn get_server_info(&self) -> Option<ServerInfo> {
+        self.pipeline.get_server_info(PRIMARY_SOURCE).await
+    }
+
+    pub async fn get_diagnostics(&self) -> Vec<StreamDiagnostics> {
+        self.context
+            .diagnostics
+            .lock()
+            .await
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    pub async fn get_stream_stats(&self) -> Vec<StreamStats> {
+        self.pipeline.get_stream_stats().await
+    }
+
+    pub fn get_queue_stats(&self) -> Vec<QueueStats> {
+        self.pipeline.get_queue_stats()
+    }
+
+    pub async fn start_recording(&self) -> Result<PathBuf, String> {
+        let mut recorder = self.context.recorder.lock().await;
+        if let Some(recording) = recorder.as_ref().filter(|recording| recording.is_active()) {
+            return Err(format!(
+                "Already recording to {}",
+                recording.get_path().display()
+            ));
         }
 
-        // Default to STRAIGHT
-        Posture::Straight
+        let writer = CaptureWriter::create_default()
+            .map_err(|e| format!("Failed to create capture file: {}", e))?;
+        let path = writer.get_path().to_path_buf();
+        *recorder = Some(CaptureRecording::start(writer));
+
+        Ok(path)
+    }
+
+    // Returns the path of the finished capture, if a recording was running
+    pub async fn stop_recording(&self) -> Option<PathBuf> {
+        let recording = self.context.recorder.lock().await.take()?;
+        Some(recording.finish().await)
+    }
+
+    // None once the recording stopped, e.g. on a write error
+    pub async fn get_recording_path(&self) -> Option<PathBuf> {
+        let recorder = self.context.recorder.lock().await;
+        recorder
+            .as_ref()
+            .filter(|recording| recording.is_active())
+            .map(|recording| recording.get_path().to_path_buf())
     }
 
-    pub async fn is_connected(&self) -> bool {
-        *self.connection_status.lock().await
+    pub async fn get_connection_status(&self) -> ConnectionStatus {
+        let sources = self.pipeline.get_sources().await;
+        let connected = sources.iter().any(|source| source.connected);
+
+        ConnectionStatus {
+            connected,
+            message: if connected {
+                "Connected to posture server".to_string()
+            } else {
+                "Not connected to server".to_string()
+            },
+            server_address: self.get_server_address().to_string(),
+            sources,
+            ..Default::default()
+        }
     }
 }
@@ -1,8 +1,16 @@
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::calibration::PostureBaseline;
+    use crate::classifier::{PostureClassifier, RuleClassifier};
     use crate::db_manager::DbManager;
+    use crate::events::{PostureIssue, PostureMetrics};
+    use crate::landmarks::{Landmark, PoseLandmarks};
     use crate::postures::Posture;
+    use crate::protocol::{self, ParseError, WireFormat};
+    use crate::reconnect::Backoff;
+    use crate::settings::{PostureThresholds, ReconnectPolicy, ServerAddress, Transport};
+    use std::time::Duration;
 
     #[test]
     fn test_posture_enum_conversion() {
@@ -22,21 +30,1392 @@ mod tests {
 
         let unknown_posture = Posture::from("INVALID");
         assert!(matches!(unknown_posture, Posture::Unknown));
+
+        let stale_posture = Posture::from(Posture::Stale.get_posture_value());
+        assert!(matches!(stale_posture, Posture::Stale));
+
+        let paused_posture = Posture::from(Posture::Paused.get_posture_value());
+        assert!(matches!(paused_posture, Posture::Paused));
+    }
+
+    #[test]
+    fn test_server_address_validation() {
+        let address = ServerAddress::tcp(" 192.168.1.20 ", 9000).expect("Valid address rejected");
+        assert_eq!(address.to_string(), "192.168.1.20:9000");
+
+        assert!(ServerAddress::tcp("", 9876).is_err());
+        assert!(ServerAddress::tcp("127.0.0.1", 0).is_err());
+        assert!(ServerAddress::unix("  ").is_err());
+
+        // The token is never sent in clear
+        let mut secured = ServerAddress {
+            auth_token: Some("s3cret".to_string()),
+            ..address.clone()
+        };
+        assert!(secured.validate().is_err());
+        secured.tls = true;
+        assert!(secured.validate().is_ok());
+        let websocket = ServerAddress {
+            transport: Transport::WebSocket,
+            url: "ws://192.168.1.20:9000".to_string(),
+            auth_token: Some("s3cret".to_string()),
+            ..ServerAddress::default()
+        };
+        assert!(websocket.validate().is_err());
+        let websocket = ServerAddress {
+            url: "wss://192.168.1.20:9000".to_string(),
+            ..websocket
+        };
+        assert!(websocket.validate().is_ok());
+
+        // Settings saved before transports existed still load as TCP
+        let legacy: ServerAddress =
+            serde_json::from_str(r#"{"host":"10.0.0.2","port":9876}"#).expect("Legacy rejected");
+        assert_eq!(legacy.transport, Transport::Tcp);
+        assert_eq!(legacy.to_string(), "10.0.0.2:9876");
+    }
+
+    #[test]
+    fn test_reconnect_backoff() {
+        let policy = ReconnectPolicy {
+            initial_delay_ms: 500,
+            max_delay_ms: 3000,
+            multiplier: 2.0,
+            jitter: 0.0,
+            max_attempts: Some(6),
+        };
+        assert!(policy.validate().is_ok());
+
+        let mut backoff = Backoff::default();
+        let delays: Vec<Option<Duration>> = (0..6).map(|_| backoff.next_delay(&policy)).collect();
+        assert_eq!(
+            delays,
+            vec![
+                Some(Duration::from_millis(500)),
+                Some(Duration::from_millis(1000)),
+                Some(Duration::from_millis(2000)),
+                Some(Duration::from_millis(3000)),
+                Some(Duration::from_millis(3000)),
+                None,
+            ]
+        );
+
+        backoff.reset();
+        assert_eq!(backoff.get_attempt(), 0);
+
+        // Jitter stays within the configured fraction and never exceeds the maximum
+        let jittered = ReconnectPolicy {
+            jitter: 0.5,
+            max_attempts: None,
+            ..policy
+        };
+        let delay = backoff
+            .next_delay(&jittered)
+            .expect("Attempts are unlimited");
+        assert!(delay >= Duration::from_millis(250) && delay <= Duration::from_millis(750));
+
+        let invalid = ReconnectPolicy {
+            jitter: 1.5,
+            ..ReconnectPolicy::default()
+        };
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn test_protocol_format_detection() {
+        let pipe_line = "0.3|0.2|0.5|0.95|0.7|0.2|0.5|0.95|0.3|0.4|0.0|0.95|0.7|0.4|0.0|0.95";
+        assert_eq!(protocol::detect_format(pipe_line), Some(WireFormat::Pipe));
+        let (landmarks, _) = protocol::parse_frame(pipe_line).expect("Pipe line rejected");
+        let metrics = PostureMetrics::from_landmarks(&landmarks).expect("Missing landmarks");
+        assert_eq!(metrics.right_shoulder.x, 0.7);
+
+        let json_line = r#"{"version":1,"fps":30,"landmarks":{
+            "left_ear":{"x":0.3,"y":0.2,"z":0.5,"visibility":0.95},
+            "right_ear":{"x":0.7,"y":0.2,"z":0.5,"visibility":0.95},
+            "left_shoulder":{"x":0.3,"y":0.4,"z":0.0,"visibility":0.95},
+            "right_shoulder":{"x":0.7,"y":0.4,"z":0.0,"visibility":0.95}}}"#
+            .replace('\n', "");
+        assert_eq!(protocol::detect_format(&json_line), Some(WireFormat::Json));
+        let (landmarks, _) = protocol::parse_frame(&json_line).expect("JSON line rejected");
+        assert_eq!(
+            landmarks.get(Landmark::LeftShoulder).map(|p| p.y),
+            Some(0.4)
+        );
+
+        // Unsupported versions are rejected
+        let future_line = json_line.replace("\"version\":1", "\"version\":99");
+        assert_eq!(
+            protocol::parse_frame(&future_line).err(),
+            Some(ParseError::UnsupportedVersion(99))
+        );
+    }
+
+    #[test]
+    fn test_strict_metric_parsing() {
+        let valid = "0.3|0.2|0.5|0.95|0.7|0.2|0.5|0.95|0.3|0.4|0.0|0.95|0.7|0.4|0.0|0.95";
+        assert!(protocol::parse_frame(valid).is_ok());
+
+        // Corrupted values are not silently turned into 0.0
+        let corrupted = valid.replacen("0.95", "0.9x", 1);
+        assert!(matches!(
+            protocol::parse_frame(&corrupted),
+            Err(ParseError::InvalidNumber { field, .. }) if field == "left_ear.visibility"
+        ));
+
+        let not_a_number = valid.replacen("0.5", "NaN", 1);
+        assert!(matches!(
+            protocol::parse_frame(&not_a_number),
+            Err(ParseError::NonFinite { field }) if field == "left_ear.z"
+        ));
+
+        let infinite = valid.replacen("0.3", "inf", 1);
+        assert!(matches!(
+            protocol::parse_frame(&infinite),
+            Err(ParseError::NonFinite { .. })
+        ));
+
+        let out_of_range = valid.replacen("0.95", "1.5", 1);
+        assert!(matches!(
+            protocol::parse_frame(&out_of_range),
+            Err(ParseError::VisibilityOutOfRange { .. })
+        ));
+
+        assert_eq!(
+            protocol::parse_frame("0.3|0.2|0.5").err(),
+            Some(ParseError::FieldCount {
+                expected: 16,
+                found: 3
+            })
+        );
+        assert_eq!(
+            protocol::parse_frame("Server listening...").err(),
+            Some(ParseError::UnknownFormat)
+        );
+
+        // Every connection starts counting from zero, with the address it connected to
+        let address = ServerAddress::default();
+        let diagnostics = crate::events::StreamDiagnostics::new("server", &address);
+        assert_eq!(diagnostics.server_address, address.to_string());
+        assert_eq!(diagnostics.good_lines + diagnostics.rejected_lines, 0);
+        assert!(diagnostics.last_error.is_none());
+    }
+
+    #[test]
+    fn test_optional_landmarks() {
+        let json_line = r#"{"version":1,"landmarks":{
+            "nose":{"x":0.5,"y":0.15,"z":0.4,"visibility":0.99},
+            "left_hip":{"x":0.35,"y":0.9,"z":0.1,"visibility":0.4},
+            "tail":{"x":0.0,"y":0.0,"z":0.0,"visibility":1.0}}}"#;
+        let (landmarks, _) = protocol::parse_frame(json_line).expect("JSON line rejected");
+
+        // Unknown landmark names are ignored
+        assert!(landmarks.get(Landmark::Nose).is_some());
+        assert!(landmarks.get(Landmark::LeftHip).is_some());
+        assert!(landmarks.get(Landmark::LeftEar).is_none());
+
+        // Landmarks are keyed by their MediaPipe name in posture-update events
+        let serialized = serde_json::to_string(&landmarks).expect("Serialization failed");
+        assert!(serialized.starts_with(r#"{"nose":{"#));
+
+        // The legacy four-point view needs both ears and both shoulders
+        assert!(PostureMetrics::from_landmarks(&landmarks).is_none());
+        assert!(PostureMetrics::from_landmarks(&PoseLandmarks::new()).is_none());
+
+        assert_eq!(Landmark::ALL.len(), 33);
+        assert_eq!(
+            Landmark::from_name("right_foot_index"),
+            Some(Landmark::RightFootIndex)
+        );
+    }
+
+    #[test]
+    fn test_posture_thresholds() {
+        // Head slightly tilted, with a slope of 0.07
+        let line = "0.6|0.3|0|0.95|0.4|0.286|0|0.95|0.7|0.5|0|0.95|0.3|0.5|0|0.95";
+        let classify = |thresholds: &PostureThresholds| {
+            let (landmarks, _) = protocol::parse_frame(line).unwrap();
+            RuleClassifier
+                .classify_frame(landmarks, thresholds, &PostureBaseline::default())
+                .posture
+        };
+
+        let mut thresholds = PostureThresholds::default();
+        assert!(thresholds.validate().is_ok());
+        assert!(matches!(classify(&thresholds), Posture::Straight));
+
+        thresholds.head_tilt_slope = 0.05;
+        assert!(matches!(classify(&thresholds), Posture::HeadTiltRight));
+
+        thresholds.min_visibility = 0.96;
+        assert!(matches!(
+            classify(&thresholds),
+            Posture::ShouldersNotVisible
+        ));
+
+        thresholds.lean_in_depth = 0.1;
+        assert!(thresholds.validate().is_err());
+        assert!(PostureThresholds {
+            min_visibility: 1.5,
+            ..PostureThresholds::default()
+        }
+        .validate()
+        .is_err());
+        assert!(PostureThresholds {
+            slouch_shoulder_depth: 0.0,
+            ..PostureThresholds::default()
+        }
+        .validate()
+        .is_err());
+        assert!(PostureThresholds {
+            side_lean_in_gap: 0.05,
+            ..PostureThresholds::default()
+        }
+        .validate()
+        .is_err());
+
+        // Ears 0.25 ahead of shoulders 0.2 closer to the camera than usual
+        let slouched = "0.6|0.3|-0.45|0.95|0.4|0.3|-0.45|0.95|0.7|0.5|-0.2|0.95|0.3|0.5|-0.2|0.95";
+        let classify = |thresholds: &PostureThresholds| {
+            let (landmarks, _) = protocol::parse_frame(slouched).unwrap();
+            RuleClassifier
+                .classify_frame(landmarks, thresholds, &PostureBaseline::default())
+                .posture
+        };
+        // A lower lean-in depth doesn't change when slouching is detected
+        let mut thresholds = PostureThresholds {
+            slouch_depth: 0.1,
+            lean_in_depth: 0.15,
+            ..PostureThresholds::default()
+        };
+        assert!(thresholds.validate().is_ok());
+        assert!(matches!(classify(&thresholds), Posture::SlouchingBack));
+        thresholds.slouch_shoulder_depth = 0.1;
+        assert!(matches!(classify(&thresholds), Posture::LeaningIn));
+
+        // Invalid sections of a settings file fall back to their defaults, the others are kept
+        let settings: crate::settings::Settings = serde_json::from_str(
+            r#"{"thresholds": {"slouch_depth": 5.0}, "stream": {"frame_timeout_ms": 0},
+                "smoothing": {"min_frames": 5}}"#,
+        )
+        .unwrap();
+        let settings = settings.validated();
+        assert_eq!(settings.thresholds, PostureThresholds::default());
+        assert_eq!(settings.stream.frame_timeout_ms, 5000);
+        assert_eq!(settings.smoothing.min_frames, 5);
+    }
+
+    #[test]
+    fn test_multi_label_classification() {
+        let classify = |line: &str| {
+            let (landmarks, _) = protocol::parse_frame(line).unwrap();
+            RuleClassifier.classify_frame(
+                landmarks,
+                &PostureThresholds::default(),
+                &PostureBaseline::default(),
+            )
+        };
+
+        // Slouching with the head tilted, the slouch stays the primary posture
+        let update = classify("0.6|0.3|-0.3|0.95|0.4|0.26|-0.3|0.95|0.7|0.5|0|0.95|0.3|0.5|0|0.95");
+        assert!(matches!(update.posture, Posture::SlouchingBack));
+        let issues: Vec<(String, f32)> = update
+            .issues
+            .iter()
+            .map(|issue| (issue.posture.get_posture_value(), issue.severity))
+            .collect();
+        assert_eq!(issues.len(), 2);
+        assert_eq!(issues[0].0, "SLOUCHING_BACK");
+        assert!((issues[0].1 - 1.5).abs() < 1e-4);
+        assert_eq!(issues[1].0, "HEAD_TILT_RIGHT");
+        assert!((issues[1].1 - 2.0).abs() < 1e-4);
+
+        let update = classify("0.6|0.3|0|0.95|0.4|0.3|0|0.95|0.7|0.5|0|0.95|0.3|0.5|0|0.95");
+        assert!(matches!(update.posture, Posture::Straight));
+        assert!(update.issues.is_empty());
+
+        // Nothing else is measured without the shoulders
+        let update = classify("0.6|0.3|-0.3|0.95|0.4|0.26|-0.3|0.95|0.7|0.5|0|0.5|0.3|0.5|0|0.95");
+        assert_eq!(update.issues.len(), 1);
+        assert!(matches!(update.posture, Posture::ShouldersNotVisible));
+    }
+
+    #[test]
+    fn test_linear_classifier() {
+        use crate::calibration::PostureMeasures;
+        use crate::classifier::{load_classifier, LinearClassifier};
+        use crate::settings::ClassifierSettings;
+
+        let model = include_str!("../fixtures/linear_model.json");
+        let classifier = LinearClassifier::parse(model).expect("Invalid model");
+        let thresholds = PostureThresholds::default();
+        let postures = |issues: Vec<PostureIssue>| {
+            issues
+                .iter()
+                .map(|issue| issue.posture.get_posture_value())
+                .collect::<Vec<_>>()
+        };
+
+        // Classified from the measures alone
+        let leaning_in = PostureMeasures {
+            depth_gap: 0.4,
+            shoulder_depth: -0.4,
+            ear_slope: 0.0,
+            shoulder_slope: 0.0,
+            side_gap: 0.0,
+        };
+        assert_eq!(
+            postures(classifier.classify(&leaning_in, &thresholds, &Posture::Straight, 0.0)),
+            vec!["LEANING_IN"]
+        );
+        assert!(classifier
+            .classify(
+                &PostureMeasures::default(),
+                &thresholds,
+                &Posture::Straight,
+                0.0
+            )
+            .is_empty());
+
+        // Only the most likely of two postures that exclude each other is kept
+        let slouching = PostureMeasures {
+            depth_gap: 0.6,
+            ..PostureMeasures::default()
+        };
+        assert_eq!(
+            postures(classifier.classify(&slouching, &thresholds, &Posture::Straight, 0.0)),
+            vec!["SLOUCHING_BACK"]
+        );
+
+        // Leaving the current posture takes a probability lowered by the hysteresis
+        let tilted = PostureMeasures {
+            ear_slope: 0.095,
+            ..PostureMeasures::default()
+        };
+        assert!(classifier
+            .classify(&tilted, &thresholds, &Posture::Straight, 0.5)
+            .is_empty());
+        assert_eq!(
+            postures(classifier.classify(&tilted, &thresholds, &Posture::HeadTiltRight, 0.5)),
+            vec!["HEAD_TILT_RIGHT"]
+        );
+        assert!(classifier
+            .classify(&tilted, &thresholds, &Posture::HeadTiltRight, 0.0)
+            .is_empty());
+
+        // Slouching with the head tilted, as in `test_multi_label_classification`
+        let (landmarks, _) = protocol::parse_frame(
+            "0.6|0.3|-0.3|0.95|0.4|0.26|-0.3|0.95|0.7|0.5|0|0.95|0.3|0.5|0|0.95",
+        )
+        .unwrap();
+        let update = classifier.classify_frame(landmarks, &thresholds, &PostureBaseline::default());
+        assert!(matches!(update.posture, Posture::SlouchingBack));
+        assert!(update.issues.iter().all(|issue| issue.severity > 1.0));
+        assert_eq!(
+            postures(update.issues),
+            vec!["SLOUCHING_BACK", "HEAD_TILT_RIGHT"]
+        );
+
+        // Loaded from the settings
+        let model_path = std::env::temp_dir().join("arrow_test_linear_model.json");
+        std::fs::write(&model_path, model).unwrap();
+        assert!(load_classifier(&ClassifierSettings::Linear {
+            model_path: model_path.to_string_lossy().to_string(),
+        })
+        .is_ok());
+        std::fs::remove_file(&model_path).unwrap();
+        assert!(load_classifier(&ClassifierSettings::Linear {
+            model_path: model_path.to_string_lossy().to_string(),
+        })
+        .is_err());
+        assert!(load_classifier(&ClassifierSettings::default()).is_ok());
+
+        assert!(LinearClassifier::parse(r#"{"labels": []}"#).is_err());
+        assert!(LinearClassifier::parse(r#"{"labels": [{"posture": "Straight"}]}"#).is_err());
+        assert!(LinearClassifier::parse(
+            r#"{"min_probability": 1.5, "labels": [{"posture": "LeaningIn"}]}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_posture_calibration() {
+        use crate::calibration::Calibration;
+        use crate::fusion::measure_frame;
+        use crate::settings::CameraView;
+        use tokio::time::Instant;
+
+        // The user sits with a head slightly tilted by the camera placement
+        let tilted = "0.6|0.3|0|0.95|0.4|0.286|0|0.95|0.7|0.5|0|0.95|0.3|0.5|0|0.95";
+        let straight = "0.6|0.3|0|0.95|0.4|0.3|0|0.95|0.7|0.5|0|0.95|0.3|0.5|0|0.95";
+        let landmarks = |line: &str| protocol::parse_frame(line).unwrap().0;
+        let thresholds = PostureThresholds {
+            head_tilt_slope: 0.05,
+            ..PostureThresholds::default()
+        };
+        let measure = |landmarks: &PoseLandmarks| {
+            measure_frame(
+                landmarks,
+                CameraView::Front,
+                thresholds.min_visibility,
+                &PostureBaseline::default(),
+            )
+        };
+
+        let now = Instant::now();
+        let mut calibration = Calibration::new(Duration::from_secs(5), now);
+        for _ in 0..20 {
+            calibration.add_measures(&measure(&landmarks(tilted)));
+        }
+        // Frames without the shoulders are not measured
+        calibration.add_measures(&measure(&PoseLandmarks::new()));
+
+        let progress = calibration.get_progress(now + Duration::from_millis(2500));
+        assert_eq!(progress.samples, 20);
+        assert!((progress.progress - 0.5).abs() < 1e-6);
+        assert!(!calibration.is_done(now + Duration::from_secs(4)));
+        assert!(calibration.is_done(now + Duration::from_secs(5)));
+
+        let baseline = calibration.finish(1000).expect("Calibration failed");
+        assert!((baseline.ear_slope - 0.07).abs() < 1e-4);
+        assert_eq!(baseline.samples, 20);
+
+        let classify = |line: &str, baseline: &PostureBaseline| {
+            RuleClassifier
+                .classify_frame(landmarks(line), &thresholds, baseline)
+                .posture
+        };
+        assert!(matches!(
+            classify(tilted, &PostureBaseline::default()),
+            Posture::HeadTiltRight
+        ));
+        assert!(matches!(classify(tilted, &baseline), Posture::Straight));
+        // Straight for the camera is now tilted the other way for this user
+        assert!(matches!(
+            classify(straight, &baseline),
+            Posture::HeadTiltLeft
+        ));
+
+        // Too few frames
+        let mut calibration = Calibration::new(Duration::from_secs(5), now);
+        calibration.add_measures(&measure(&landmarks(tilted)));
+        assert!(calibration.finish(1000).is_err());
+
+        // Moving around
+        let mut calibration = Calibration::new(Duration::from_secs(5), now);
+        for i in 0..20 {
+            let line = if i % 2 == 0 {
+                straight
+            } else {
+                "0.6|0.3|0|0.95|0.4|0.2|0|0.95|0.7|0.5|0|0.95|0.3|0.5|0|0.95"
+            };
+            calibration.add_measures(&measure(&landmarks(line)));
+        }
+        assert!(calibration.finish(1000).is_err());
+    }
+
+    #[test]
+    fn test_posture_smoothing() {
+        use crate::settings::SmoothingSettings;
+        use crate::smoothing::PostureSmoother;
+        use tokio::time::Instant;
+
+        let settings = SmoothingSettings {
+            min_dwell_ms: 1000,
+            min_frames: 3,
+            hysteresis: 0.5,
+        };
+        assert!(settings.validate().is_ok());
+        let now = Instant::now();
+        let at = |ms: u64| now + Duration::from_millis(ms);
+
+        // The first posture is accepted right away
+        let mut smoother = PostureSmoother::default();
+        assert!(smoother.accept(&Posture::Unknown, &Posture::Straight, at(0), &settings));
+
+        // Enough frames, but not for long enough
+        for ms in [0, 100, 200, 300] {
+            assert!(!smoother.accept(&Posture::Straight, &Posture::LeaningIn, at(ms), &settings));
+        }
+        // A single frame back to straight starts over
+        assert!(!smoother.accept(&Posture::Straight, &Posture::Straight, at(400), &settings));
+        assert!(!smoother.accept(&Posture::Straight, &Posture::LeaningIn, at(500), &settings));
+        assert!(!smoother.accept(&Posture::Straight, &Posture::LeaningIn, at(1400), &settings));
+        assert!(smoother.accept(&Posture::Straight, &Posture::LeaningIn, at(1500), &settings));
+
+        // Long enough, but not for enough frames
+        let mut smoother = PostureSmoother::default();
+        assert!(!smoother.accept(
+            &Posture::Straight,
+            &Posture::SlouchingBack,
+            at(0),
+            &settings
+        ));
+        assert!(!smoother.accept(
+            &Posture::Straight,
+            &Posture::SlouchingBack,
+            at(5000),
+            &settings
+        ));
+        assert!(smoother.accept(
+            &Posture::Straight,
+            &Posture::SlouchingBack,
+            at(5100),
+            &settings
+        ));
+
+        // A head slope of 0.04 is below the threshold to enter the tilt, but not to leave it
+        let line = "0.6|0.3|0|0.95|0.4|0.292|0|0.95|0.7|0.5|0|0.95|0.3|0.5|0|0.95";
+        let thresholds = PostureThresholds {
+            head_tilt_slope: 0.05,
+            ..PostureThresholds::default()
+        };
+        let classify = |thresholds: &PostureThresholds| {
+            let (landmarks, _) = protocol::parse_frame(line).unwrap();
+            RuleClassifier
+                .classify_frame(landmarks, thresholds, &PostureBaseline::default())
+                .posture
+        };
+        assert!(matches!(classify(&thresholds), Posture::Straight));
+        let exit = thresholds.with_hysteresis(&Posture::HeadTiltRight, settings.hysteresis);
+        assert!((exit.head_tilt_slope - 0.025).abs() < 1e-6);
+        assert!(matches!(classify(&exit), Posture::HeadTiltRight));
+        assert_eq!(
+            thresholds.with_hysteresis(&Posture::Straight, settings.hysteresis),
+            thresholds
+        );
+
+        assert!(SmoothingSettings {
+            min_frames: 0,
+            ..SmoothingSettings::default()
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn test_stream_stats() {
+        use crate::protocol::FrameMetadata;
+        use crate::stream_stats::StreamStatsTracker;
+        use tokio::time::Instant;
+
+        let line = r#"{"version":1,"seq":42,"captured_at":1700000000123.5,"landmarks":{}}"#;
+        let (_, metadata) = protocol::parse_frame(line).expect("JSON line rejected");
+        assert_eq!(metadata.seq, Some(42));
+        assert_eq!(metadata.captured_at, Some(1700000000123.5));
+        let (_, metadata) = protocol::parse_frame(&"0.5|".repeat(16)[..63]).unwrap();
+        assert_eq!(metadata, FrameMetadata::default());
+
+        // 10 frames per second, frame 4 is dropped and frame 3 arrives twice
+        let start = Instant::now();
+        let mut tracker = StreamStatsTracker::default();
+        for (index, seq) in [1, 2, 3, 5, 3, 6, 7].into_iter().enumerate() {
+            let metadata = FrameMetadata {
+                seq: Some(seq),
+                captured_at: None,
+            };
+            tracker.record_frame(&metadata, start + Duration::from_millis(index as u64 * 100));
+        }
+        tracker.record_latency(20.0);
+        tracker.record_latency(40.0);
+
+        let now = start + Duration::from_millis(600);
+        let stats = tracker.get_stats("primary", now);
+        assert_eq!(stats.frames, 7);
+        assert!((stats.frames_per_second - 10.0).abs() < 0.01);
+        assert_eq!((stats.dropped_frames, stats.gaps), (1, 1));
+        assert_eq!(stats.out_of_order_frames, 1);
+        assert_eq!(stats.latency_ms, Some(30.0));
+        assert_eq!(stats.max_latency_ms, Some(40.0));
+
+        // A new connection numbers its frames from the start again
+        tracker.reset_sequence();
+        tracker.record_frame(
+            &FrameMetadata {
+                seq: Some(1),
+                captured_at: None,
+            },
+            now,
+        );
+        assert_eq!(tracker.get_stats("primary", now).out_of_order_frames, 1);
+
+        // No frame for a while
+        let later = tracker.get_stats("primary", now + Duration::from_secs(10));
+        assert_eq!(later.frames_per_second, 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_stage_queue_backpressure() {
+        use crate::stage_queue::StageQueue;
+
+        let (queue, mut receiver) = StageQueue::new("frames", 2);
+        assert!(queue.push(1));
+        assert!(queue.push(2));
+
+        // A full queue drops instead of waiting for the consumer
+        assert!(!queue.push(3));
+        let stats = queue.get_stats();
+        assert_eq!((stats.depth, stats.capacity, stats.dropped), (2, 2, 1));
+
+        assert_eq!(receiver.recv().await, Some(1));
+        assert_eq!(queue.get_stats().depth, 1);
+        assert!(queue.clone().push(4));
+        assert_eq!(receiver.recv().await, Some(2));
+        assert_eq!(receiver.recv().await, Some(4));
+        assert_eq!(queue.get_stats().dropped, 1);
+    }
+
+    #[test]
+    fn test_multi_source_fusion() {
+        use crate::events::Point3D;
+        use crate::fusion::Fusion;
+        use crate::settings::{CameraView, SourceConfig};
+        use tokio::time::Instant;
+
+        let point = |x: f32, y: f32, z: f32| Point3D {
+            x,
+            y,
+            z,
+            visibility: 0.9,
+        };
+        let max_age = Duration::from_secs(5);
+        let now = Instant::now();
+        let baseline = PostureBaseline::default();
+
+        // Ears 0.1 closer to the camera than the shoulders, both level
+        let mut front = PoseLandmarks::new();
+        front.insert(Landmark::LeftEar, point(0.6, 0.3, 0.0));
+        front.insert(Landmark::RightEar, point(0.4, 0.3, 0.0));
+        front.insert(Landmark::LeftShoulder, point(0.7, 0.5, 0.1));
+        front.insert(Landmark::RightShoulder, point(0.3, 0.5, 0.1));
+        // Seen from the left, the ear is 0.2 ahead of the shoulder
+        let mut side = PoseLandmarks::new();
+        side.insert(Landmark::LeftEar, point(0.4, 0.3, 0.5));
+        side.insert(Landmark::LeftShoulder, point(0.6, 0.5, 0.2));
+
+        let mut fusion = Fusion::default();
+        fusion.update("front", 1.0, CameraView::Front, front.clone(), now);
+
+        // A single front camera is measured as is
+        let fused = fusion.fuse(now, max_age, 0.5, &baseline);
+        let measures = fused.measures.unwrap();
+        assert!((measures.depth_gap - 0.1).abs() < 1e-5);
+        assert!((measures.shoulder_depth - 0.1).abs() < 1e-5);
+        assert_eq!(fused.landmarks.get(Landmark::LeftEar).unwrap().x, 0.6);
+
+        // The side camera measures how far forward the head is, apart from the depths
+        fusion.update("side", 1.0, CameraView::Left, side.clone(), now);
+        let fused = fusion.fuse(now, max_age, 0.5, &baseline);
+        let measures = fused.measures.unwrap();
+        assert!((measures.depth_gap - 0.1).abs() < 1e-5);
+        assert!((measures.side_gap - 0.2).abs() < 1e-5);
+        assert!((measures.shoulder_depth - 0.1).abs() < 1e-5);
+        assert!(measures.ear_slope.abs() < 1e-5);
+        // The front camera is the one shown
+        assert_eq!(fused.landmarks.get(Landmark::RightEar).unwrap().x, 0.4);
+
+        // A camera on the right sees the user facing the other way
+        let mut mirrored = Fusion::default();
+        let mut right = PoseLandmarks::new();
+        right.insert(Landmark::RightEar, point(0.5, 0.3, 0.5));
+        right.insert(Landmark::RightShoulder, point(0.4, 0.5, 0.2));
+        mirrored.update("right", 1.0, CameraView::Right, right.clone(), now);
+        let measures = mirrored
+            .fuse(now, max_age, 0.5, &baseline)
+            .measures
+            .unwrap();
+        assert!((measures.side_gap - 0.1).abs() < 1e-5);
+
+        // Source weights scale the visibility weighting
+        fusion.update("side", 3.0, CameraView::Left, side.clone(), now);
+        fusion.update("right", 1.0, CameraView::Right, right, now);
+        let measures = fusion.fuse(now, max_age, 0.5, &baseline).measures.unwrap();
+        assert!((measures.side_gap - 0.175).abs() < 1e-5);
+        fusion.remove("right");
+
+        // The same slouch seen from the front and from the left is classified the same,
+        // however much more one camera is trusted than the other
+        let thresholds = PostureThresholds::default();
+        let mut slouched_front = front.clone();
+        slouched_front.insert(Landmark::LeftShoulder, point(0.7, 0.5, 0.3));
+        slouched_front.insert(Landmark::RightShoulder, point(0.3, 0.5, 0.3));
+        let mut slouched_side = PoseLandmarks::new();
+        slouched_side.insert(Landmark::LeftEar, point(0.52, 0.3, 0.5));
+        slouched_side.insert(Landmark::LeftShoulder, point(0.6, 0.5, 0.2));
+        let classify = |fusion: &Fusion| {
+            let measured = fusion.fuse(now, max_age, 0.5, &baseline).measures;
+            RuleClassifier
+                .get_issues(&measured, &thresholds, &baseline, &Posture::Straight, 0.0)
+                .into_iter()
+                .map(|issue| issue.posture.get_posture_value())
+                .collect::<Vec<_>>()
+        };
+        let mut slouching = Fusion::default();
+        slouching.update("front", 1.0, CameraView::Front, slouched_front, now);
+        assert_eq!(classify(&slouching), vec!["SLOUCHING_BACK"]);
+        for weight in [0.01, 1.0, 100.0] {
+            slouching.update("side", weight, CameraView::Left, slouched_side.clone(), now);
+            let measures = slouching
+                .fuse(now, max_age, 0.5, &baseline)
+                .measures
+                .unwrap();
+            assert!((measures.depth_gap - 0.3).abs() < 1e-5);
+            assert!((measures.side_gap - 0.08).abs() < 1e-5);
+            assert_eq!(classify(&slouching), vec!["SLOUCHING_BACK"]);
+        }
+        slouching.remove("front");
+        assert_eq!(classify(&slouching), vec!["SLOUCHING_BACK"]);
+
+        // Landmarks below the minimum visibility are not measured
+        assert!(matches!(
+            fusion.fuse(now, max_age, 0.95, &baseline).measures,
+            Err(Posture::ShouldersNotVisible)
+        ));
+        let mut headless = front.clone();
+        headless.insert(
+            Landmark::LeftEar,
+            Point3D {
+                visibility: 0.1,
+                ..point(0.6, 0.3, 0.0)
+            },
+        );
+        fusion.update("front", 1.0, CameraView::Front, headless, now);
+        fusion.update("side", 1.0, CameraView::Left, PoseLandmarks::new(), now);
+        assert!(matches!(
+            fusion.fuse(now, max_age, 0.5, &baseline).measures,
+            Err(Posture::HeadNotVisible)
+        ));
+
+        // Frames older than the frame timeout no longer count
+        let later = now + Duration::from_secs(6);
+        assert!(!fusion.has_fresh_frames(later, max_age));
+        let fused = fusion.fuse(later, max_age, 0.5, &baseline);
+        assert!(fused.landmarks.get(Landmark::RightEar).is_none());
+        assert!(fused.measures.is_err());
+
+        // Without a front camera the slopes are those of the baseline
+        fusion.remove("front");
+        fusion.update("side", 1.0, CameraView::Left, side, now);
+        let calibrated = PostureBaseline {
+            ear_slope: 0.07,
+            ..PostureBaseline::default()
+        };
+        let measures = fusion
+            .fuse(now, max_age, 0.5, &calibrated)
+            .measures
+            .unwrap();
+        assert!((measures.side_gap - 0.2).abs() < 1e-5);
+        assert_eq!(measures.depth_gap, 0.0);
+        assert_eq!(measures.ear_slope, 0.07);
+
+        let source = |name: &str, weight: f32| SourceConfig {
+            name: name.to_string(),
+            weight,
+            ..Default::default()
+        };
+        assert!(SourceConfig::validate_all(&[source("side", 1.5), source("top", 1.0)]).is_ok());
+        assert!(SourceConfig::validate_all(&[source("side", 1.0), source("side", 1.0)]).is_err());
+        assert!(source("primary", 1.0).validate().is_err());
+        assert!(source("", 1.0).validate().is_err());
+        assert!(source("side", 0.0).validate().is_err());
+    }
+
+    #[test]
+    fn test_server_handshake() {
+        let hello = r#"{"type":"hello","server_name":"arrow-test-server","server_version":"0.1.0",
+            "protocol_version":1,"frame_rate":30.0,
+            "landmarks":["nose","left_ear","right_ear","left_shoulder","right_shoulder"]}"#;
+        let Some(protocol::ServerMessage::Hello(info)) = protocol::parse_server_message(hello)
+        else {
+            panic!("Hello rejected");
+        };
+        assert_eq!(info.server_name, "arrow-test-server");
+        assert!(protocol::check_compatibility(&info).is_ok());
+
+        // Metric lines are not mistaken for a hello
+        assert!(protocol::parse_server_message("0.3|0.2|0.5|0.95").is_none());
+
+        let refused = r#"{"type":"error","code":"unauthorized","message":"Invalid token"}"#;
+        assert!(matches!(
+            protocol::parse_server_message(refused),
+            Some(protocol::ServerMessage::Error(error)) if error.code == "unauthorized"
+        ));
+
+        // The token is only sent when one is configured
+        let hello = serde_json::to_string(&protocol::ClientHello::new(None)).unwrap();
+        assert!(!hello.contains("auth_token"));
+        let hello =
+            serde_json::to_string(&protocol::ClientHello::new(Some("s3cret".into()))).unwrap();
+        assert!(hello.contains(r#""auth_token":"s3cret""#));
+
+        let mut newer = info.clone();
+        newer.protocol_version = protocol::PROTOCOL_VERSION + 1;
+        assert!(protocol::check_compatibility(&newer).is_err());
+
+        let mut partial = info;
+        partial.landmarks.retain(|landmark| landmark != "left_ear");
+        assert!(protocol::check_compatibility(&partial).is_err());
+    }
+
+    #[test]
+    fn test_control_commands() {
+        use crate::protocol::{ClientCommand, ControlCommand};
+
+        let command = ClientCommand::new(3, ControlCommand::SetFrameRate { frame_rate: 15.0 });
+        let line: serde_json::Value =
+            serde_json::from_str(&serde_json::to_string(&command).unwrap()).unwrap();
+        assert_eq!(
+            line,
+            serde_json::json!({"type": "command", "id": 3, "command": "set_frame_rate", "frame_rate": 15.0})
+        );
+        let line = serde_json::to_string(&ClientCommand::new(4, ControlCommand::Pause)).unwrap();
+        assert_eq!(line, r#"{"type":"command","id":4,"command":"pause"}"#);
+
+        let ack = r#"{"type":"ack","id":3,"ok":true,"message":"Frame rate set to 15"}"#;
+        assert!(matches!(
+            protocol::parse_server_message(ack),
+            Some(protocol::ServerMessage::Ack(ack)) if ack.id == 3 && ack.ok
+        ));
+        let Some(protocol::ServerMessage::Ack(ack)) =
+            protocol::parse_server_message(r#"{"type":"ack","id":5,"ok":false}"#)
+        else {
+            panic!("Ack without a message rejected");
+        };
+        assert!(!ack.ok && ack.message.is_empty());
+
+        // Servers from before the control channel accept no command
+        let hello = r#"{"type":"hello","server_name":"old","server_version":"0.0.1",
+            "protocol_version":1,"frame_rate":30.0,"landmarks":[]}"#;
+        let Some(protocol::ServerMessage::Hello(info)) = protocol::parse_server_message(hello)
+        else {
+            panic!("Hello rejected");
+        };
+        assert!(info.commands.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_server_process() {
+        use crate::server_process;
+        use crate::settings::ServerProcessSettings;
+
+        // Disabled settings don't need a script
+        let mut settings = ServerProcessSettings::default();
+        assert!(settings.validate().is_ok());
+        settings.enabled = true;
+        assert!(settings.validate().is_err());
+
+        let script_path =
+            std::env::temp_dir().join(format!("arrow-test-{}-server.sh", std::process::id()));
+        std::fs::write(
+            &script_path,
+            "[ \"$ARROW_TEST\" = yes ] || exit 1\n[ -f \"$(basename \"$0\")\" ] || exit 2\nexit \"$1\"\n",
+        )
+        .unwrap();
+
+        settings.interpreter = "sh".to_string();
+        settings.script_path = script_path.to_str().unwrap().to_string();
+        settings.args = vec!["3".to_string()];
+        settings
+            .env
+            .insert("ARROW_TEST".to_string(), "yes".to_string());
+        assert!(settings.validate().is_ok());
+
+        // Runs in the directory of the script, with the arguments and environment from the settings
+        let mut child = server_process::spawn_server(&settings).expect("Failed to spawn");
+        let status = child.wait().await.unwrap();
+        assert_eq!(status.code(), Some(3));
+
+        settings
+            .env
+            .insert("ARROW=TEST".to_string(), "yes".to_string());
+        assert!(settings.validate().is_err());
+
+        let _ = std::fs::remove_file(&script_path);
+    }
+
+    #[tokio::test]
+    async fn test_server_discovery() {
+        use crate::discovery::{self, DiscoveredServers};
+        use crate::settings::DiscoverySettings;
+        use tokio::net::UdpSocket;
+        use tokio::time::{timeout, Instant};
+
+        let settings = DiscoverySettings {
+            port: 0,
+            multicast_group: None,
+            ..DiscoverySettings::default()
+        };
+        let socket = discovery::bind_socket(&settings)
+            .await
+            .expect("Failed to bind");
+        let port = socket.local_addr().unwrap().port();
+
+        // Anything else sent to the port is skipped
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        server
+            .send_to(b"0.3|0.2", ("127.0.0.1", port))
+            .await
+            .unwrap();
+        let beacon = r#"{"type":"beacon","server_name":"desk","server_version":"1.0","protocol_version":1,"port":9876}"#;
+        server
+            .send_to(beacon.as_bytes(), ("127.0.0.1", port))
+            .await
+            .unwrap();
+
+        let (beacon, sender) = timeout(Duration::from_secs(5), discovery::receive_beacon(&socket))
+            .await
+            .expect("No beacon received")
+            .unwrap();
+        assert_eq!(beacon.server_name, "desk");
+
+        let mut servers = DiscoveredServers::default();
+        let now = Instant::now();
+        let (found, changed) = servers.record(beacon.clone(), sender.ip(), now);
+        assert!(changed);
+        assert_eq!(found.address.to_string(), "127.0.0.1:9876");
+        assert!(found.incompatibility.is_none());
+
+        let (_, changed) =
+            servers.record(beacon.clone(), sender.ip(), now + Duration::from_secs(1));
+        assert!(!changed);
+
+        let newer = protocol::ServerBeacon {
+            protocol_version: protocol::PROTOCOL_VERSION + 1,
+            port: 9877,
+            ..beacon
+        };
+        let (found, changed) = servers.record(newer, sender.ip(), now);
+        assert!(changed);
+        assert!(found.incompatibility.is_some());
+        assert_eq!(servers.get_servers(now).len(), 2);
+
+        // Only the server announced within the expiry is kept
+        let later = now + Duration::from_millis(10500);
+        assert!(servers.expire(later, Duration::from_secs(10)));
+        let remaining = servers.get_servers(later);
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].address.port, 9876);
+        assert_eq!(remaining[0].last_seen_ms, 9500);
+
+        // Auto-connect never leaves a private server for a less private one
+        let announced = remaining[0].address.clone();
+        let tls = ServerAddress {
+            tls: true,
+            ..announced.clone()
+        };
+        assert!(discovery::can_auto_connect(
+            &ServerAddress::default(),
+            &announced
+        ));
+        assert!(!discovery::can_auto_connect(&tls, &announced));
+        assert!(discovery::can_auto_connect(&tls, &tls));
+        let pinned = ServerAddress {
+            pinned_certificate: Some("ab".repeat(32)),
+            ..tls.clone()
+        };
+        assert!(!discovery::can_auto_connect(&pinned, &tls));
+
+        assert!(DiscoverySettings::default().validate().is_ok());
+        assert!(DiscoverySettings {
+            multicast_group: Some("192.168.1.20".parse().unwrap()),
+            ..DiscoverySettings::default()
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_unix_socket_transport() {
+        use crate::transport;
+        use tokio::io::AsyncWriteExt;
+        use tokio::net::UnixListener;
+
+        let socket_path =
+            std::env::temp_dir().join(format!("arrow-test-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&socket_path);
+        let listener = UnixListener::bind(&socket_path).expect("Failed to bind socket");
+
+        let address = ServerAddress::unix(socket_path.to_str().unwrap()).expect("Invalid path");
+        let (stream, accepted) = tokio::join!(transport::connect(&address), listener.accept());
+        let (mut server, _) = accepted.expect("Failed to accept");
+        server.write_all(b"0.3|0.2|0.5|0.95\r\n").await.unwrap();
+
+        let mut connection = stream.expect("Failed to connect");
+        let line = connection.read_line().await.unwrap();
+        assert_eq!(line.as_deref(), Some("0.3|0.2|0.5|0.95"));
+
+        let _ = std::fs::remove_file(&socket_path);
+    }
+
+    #[tokio::test]
+    async fn test_websocket_transport() {
+        use crate::transport;
+        use futures_util::{SinkExt, StreamExt};
+        use tokio::net::TcpListener;
+        use tokio_tungstenite::tungstenite::Message;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        // Local WebSocket posture server sending one text frame and one binary frame
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+            socket
+                .send(Message::text("0.1|0.2|0.3|0.9\n0.4|0.5|0.6|0.9\n"))
+                .await
+                .unwrap();
+            socket
+                .send(Message::binary(b"0.7|0.8|0.9|0.9".to_vec()))
+                .await
+                .unwrap();
+
+            // The client hello comes back as a text frame
+            match socket.next().await {
+                Some(Ok(Message::Text(text))) => text.to_string(),
+                other => panic!("Unexpected message: {:?}", other),
+            }
+        });
+
+        let address = ServerAddress::websocket(&format!("ws://127.0.0.1:{}/posture", port))
+            .expect("Invalid URL");
+        let mut connection = transport::connect(&address)
+            .await
+            .expect("Failed to connect");
+        connection.write_line(r#"{"type":"hello"}"#).await.unwrap();
+
+        let mut lines = Vec::new();
+        for _ in 0..3 {
+            lines.push(connection.read_line().await.unwrap().unwrap());
+        }
+        assert_eq!(
+            lines,
+            vec!["0.1|0.2|0.3|0.9", "0.4|0.5|0.6|0.9", "0.7|0.8|0.9|0.9"]
+        );
+        assert_eq!(server.await.unwrap(), r#"{"type":"hello"}"#);
+
+        assert!(ServerAddress::websocket("http://127.0.0.1:9876").is_err());
+        assert!(ServerAddress::websocket("ws://").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tls_certificate_pinning() {
+        use crate::{tls, transport};
+        use std::sync::Arc;
+        use tokio::io::AsyncWriteExt;
+        use tokio::net::TcpListener;
+        use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+        use tokio_rustls::rustls::{crypto, ServerConfig};
+        use tokio_rustls::TlsAcceptor;
+
+        // Self-signed certificate like the one on a LAN posture server
+        let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()])
+            .expect("Failed to generate certificate");
+        let certificate = CertificateDer::from(certified.cert.der().to_vec());
+        let fingerprint = tls::get_certificate_fingerprint(&certificate);
+        let key = PrivateKeyDer::try_from(certified.key_pair.serialize_der()).unwrap();
+
+        let config =
+            ServerConfig::builder_with_provider(Arc::new(crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .unwrap()
+                .with_no_client_auth()
+                .with_single_cert(vec![certificate], key)
+                .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                if let Ok(mut stream) = acceptor.accept(stream).await {
+                    let _ = stream.write_all(b"0.1|0.2|0.3|0.9\n").await;
+                    let _ = stream.shutdown().await;
+                }
+            }
+        });
+
+        let mut address = ServerAddress::tcp("localhost", port).unwrap();
+        address.tls = true;
+
+        // Not signed by a public CA
+        assert!(transport::connect(&address).await.is_err());
+
+        // Pinned, in the colon separated notation most tools print
+        let pinned = fingerprint
+            .as_bytes()
+            .chunks(2)
+            .map(|pair| std::str::from_utf8(pair).unwrap().to_uppercase())
+            .collect::<Vec<_>>()
+            .join(":");
+        address.pinned_certificate = Some(pinned);
+        assert!(address.validate().is_ok());
+        let mut connection = transport::connect(&address)
+            .await
+            .expect("Pinned certificate rejected");
+        assert_eq!(
+            connection.read_line().await.unwrap().as_deref(),
+            Some("0.1|0.2|0.3|0.9")
+        );
+
+        address.pinned_certificate = Some("0".repeat(64));
+        let error = transport::connect(&address).await.err().unwrap();
+        assert!(error.to_string().contains("pinned certificate"));
+
+        address.pinned_certificate = Some("not-a-fingerprint".to_string());
+        assert!(address.validate().is_err());
+
+        let mut unix = ServerAddress::unix("/tmp/arrow.sock").unwrap();
+        unix.tls = true;
+        assert!(unix.validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_capture_replay_classification() {
+        use crate::capture::{CaptureRecording, CaptureReplay, CaptureWriter};
+
+        // Recorded session, replayed as fast as possible so postures are checked deterministically
+        let capture_path =
+            std::env::temp_dir().join(format!("arrow-test-{}.capture.jsonl", std::process::id()));
+        std::fs::write(
+            &capture_path,
+            include_str!("../fixtures/session.capture.jsonl"),
+        )
+        .unwrap();
+
+        let mut replay = CaptureReplay::open(&capture_path, Some("primary".to_string()), 1e6)
+            .await
+            .expect("Failed to open capture");
+        let mut lines = Vec::new();
+        while let Some(line) = replay.read_line().await.unwrap() {
+            lines.push(line);
+        }
+
+        // The handshake is replayed too, the side camera lines are not
+        assert_eq!(lines.len(), 9);
+        assert!(protocol::parse_server_message(&lines[0]).is_some());
+
+        let postures: Vec<String> = lines
+            .iter()
+            .filter_map(|line| protocol::parse_frame(line).ok())
+            .map(|(landmarks, _)| {
+                RuleClassifier
+                    .classify_frame(
+                        landmarks,
+                        &PostureThresholds::default(),
+                        &PostureBaseline::default(),
+                    )
+                    .posture
+                    .get_posture_value()
+            })
+            .collect();
+        assert_eq!(
+            postures,
+            vec![
+                "STRAIGHT",
+                "STRAIGHT",
+                "SLOUCHING_BACK",
+                "SLOUCHING_BACK",
+                "HEAD_TILT_RIGHT",
+                "SHOULDERS_NOT_VISIBLE",
+                "STRAIGHT"
+            ]
+        );
+
+        // A fresh recording plays back the same lines
+        let writer = CaptureWriter::create(&capture_path).expect("Failed to create capture");
+        let recording = CaptureRecording::start(writer);
+        recording.record("primary", "0.1|0.2|0.3|0.9");
+        recording.record("side", "0.4|0.5|0.6|0.9");
+        assert_eq!(recording.finish().await, capture_path);
+
+        let mut replay = CaptureReplay::open(&capture_path, None, 1e6).await.unwrap();
+        assert_eq!(
+            replay.read_line().await.unwrap().as_deref(),
+            Some("0.1|0.2|0.3|0.9")
+        );
+        assert_eq!(
+            replay.read_line().await.unwrap().as_deref(),
+            Some("0.4|0.5|0.6|0.9")
+        );
+        assert!(replay.read_line().await.unwrap().is_none());
+
+        let address = ServerAddress::replay(capture_path.to_str().unwrap(), 2.0);
+        assert!(address.is_ok());
+        assert!(ServerAddress::replay(capture_path.to_str().unwrap(), 0.0).is_err());
+        assert!(ServerAddress::replay(capture_path.to_str().unwrap(), 1e-30).is_err());
+        let _ = std::fs::remove_file(&capture_path);
+        assert!(ServerAddress::replay(capture_path.to_str().unwrap(), 1.0).is_err());
+    }
+
+    #[test]
+    fn test_demo_scenarios() {
+        use crate::demo::DemoGenerator;
+        use crate::settings::{DemoScenario, DemoSettings};
+
+        let demo = |scenario: DemoScenario| DemoSettings {
+            enabled: true,
+            scenario,
+            dropouts_per_minute: 0.0,
+            ..Default::default()
+        };
+        let classify = |generator: &mut DemoGenerator, seconds: f32| {
+            generator
+                .frame_at(Duration::from_secs_f32(seconds))
+                .map(|landmarks| {
+                    RuleClassifier
+                        .classify_frame(
+                            landmarks,
+                            &PostureThresholds::default(),
+                            &PostureBaseline::default(),
+                        )
+                        .posture
+                        .get_posture_value()
+                })
+        };
+
+        // Every posture can be held, noise included
+        for posture in [
+            Posture::Straight,
+            Posture::SlouchingBack,
+            Posture::LeaningIn,
+            Posture::HeadTiltLeft,
+            Posture::HeadTiltRight,
+            Posture::BodyTiltLeft,
+            Posture::BodyTiltRight,
+            Posture::ShouldersNotVisible,
+            Posture::HeadNotVisible,
+        ] {
+            let expected = posture.get_posture_value();
+            let mut generator = DemoGenerator::new(demo(DemoScenario::Steady { posture }), 7);
+            for second in 2..60 {
+                assert_eq!(
+                    classify(&mut generator, second as f32).as_deref(),
+                    Some(expected.as_str())
+                );
+            }
+        }
+
+        // A frozen stream produces no frames at all
+        let mut generator = DemoGenerator::new(
+            demo(DemoScenario::Steady {
+                posture: Posture::Stale,
+            }),
+            7,
+        );
+        assert!(classify(&mut generator, 5.0).is_none());
+
+        let mut generator = DemoGenerator::new(demo(DemoScenario::Cycle { hold_secs: 10 }), 7);
+        assert_eq!(classify(&mut generator, 5.0).as_deref(), Some("STRAIGHT"));
+        assert_eq!(
+            classify(&mut generator, 15.0).as_deref(),
+            Some("SLOUCHING_BACK")
+        );
+        assert!(classify(&mut generator, 175.0).is_none());
+
+        let mut generator = DemoGenerator::new(
+            demo(DemoScenario::SlouchAfter {
+                straight_minutes: 20,
+            }),
+            7,
+        );
+        assert_eq!(
+            classify(&mut generator, 1190.0).as_deref(),
+            Some("STRAIGHT")
+        );
+        assert_eq!(
+            classify(&mut generator, 1260.0).as_deref(),
+            Some("SLOUCHING_BACK")
+        );
+
+        let mut generator = DemoGenerator::new(
+            demo(DemoScenario::FrequentHeadTilts { interval_secs: 10 }),
+            7,
+        );
+        assert_eq!(classify(&mut generator, 3.0).as_deref(), Some("STRAIGHT"));
+        assert_eq!(
+            classify(&mut generator, 9.0).as_deref(),
+            Some("HEAD_TILT_LEFT")
+        );
+        assert_eq!(classify(&mut generator, 14.0).as_deref(), Some("STRAIGHT"));
+        assert_eq!(
+            classify(&mut generator, 19.0).as_deref(),
+            Some("HEAD_TILT_RIGHT")
+        );
+
+        // With a dropout on every frame, the ears or shoulders are never visible
+        let mut settings = demo(DemoScenario::Steady {
+            posture: Posture::Straight,
+        });
+        settings.dropouts_per_minute = 60.0;
+        settings.frame_rate = 1.0;
+        let mut generator = DemoGenerator::new(settings, 7);
+        let posture = classify(&mut generator, 5.0).unwrap();
+        assert!(posture == "HEAD_NOT_VISIBLE" || posture == "SHOULDERS_NOT_VISIBLE");
+
+        assert!(demo(DemoScenario::Cycle { hold_secs: 10 })
+            .validate()
+            .is_ok());
+        assert!(demo(DemoScenario::Cycle { hold_secs: 0 })
+            .validate()
+            .is_err());
+        assert!(demo(DemoScenario::Steady {
+            posture: Posture::Unknown
+        })
+        .validate()
+        .is_err());
+        assert!(demo(DemoScenario::Steady {
+            posture: Posture::Paused
+        })
+        .validate()
+        .is_err());
     }
 
     #[tokio::test]
     async fn test_database_operations() {
         // Create a temporary database for testing
         let db_manager = DbManager::new().expect("Failed to create database");
-        
+
         // Test session start
         assert!(db_manager.log_session_start().is_ok());
 
         // Test posture change logging
-        assert!(db_manager.log_posture_change("STRAIGHT", "SLOUCHING_BACK").is_ok());
-        assert!(db_manager.log_posture_change("SLOUCHING_BACK", "STRAIGHT").is_ok());
+        assert!(db_manager
+            .log_posture_change("STRAIGHT", "SLOUCHING_BACK", &[])
+            .is_ok());
+        assert!(db_manager.get_last_change_issues().unwrap().is_empty());
+
+        // Co-occurring issues are logged with the change
+        let issues = [
+            PostureIssue {
+                posture: Posture::SlouchingBack,
+                severity: 1.5,
+            },
+            PostureIssue {
+                posture: Posture::HeadTiltRight,
+                severity: 1.2,
+            },
+        ];
+        assert!(db_manager
+            .log_posture_change("SLOUCHING_BACK", "STRAIGHT", &issues)
+            .is_ok());
+        assert_eq!(
+            db_manager.get_last_change_issues().unwrap(),
+            vec![
+                ("SLOUCHING_BACK".to_string(), 1.5),
+                ("HEAD_TILT_RIGHT".to_string(), 1.2)
+            ]
+        );
+
+        // Test stale stream logging
+        assert!(db_manager.log_stream_stale("SLOUCHING_BACK").is_ok());
+
+        // Test monitoring pause logging
+        assert!(db_manager.log_monitoring_paused("STRAIGHT").is_ok());
+        assert!(db_manager.get_weekly_stats().is_ok());
 
         // Test session end
         assert!(db_manager.log_session_end("STRAIGHT").is_ok());
     }
-}
\ No newline at end of file
+}
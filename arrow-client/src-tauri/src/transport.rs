@@ -0,0 +1,170 @@
+use crate::capture::CaptureReplay;
+use crate::settings::{ServerAddress, Transport};
+use crate::tls;
+use futures_util::{SinkExt, StreamExt};
+use std::collections::VecDeque;
+use std::io;
+use std::path::Path;
+use tokio::io::{
+    AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, Lines, ReadHalf, WriteHalf,
+};
+use tokio::net::TcpStream;
+#[cfg(unix)]
+use tokio::net::UnixStream;
+use tokio_tungstenite::tungstenite::http::Uri;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+// A connected byte stream to the posture server, whatever the transport
+pub trait PostureStream: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> PostureStream for T {}
+
+/*
+A connection to the posture server that exchanges protocol lines.
+Byte streams (TCP, Unix sockets) carry newline-terminated lines.
+WebSocket text and binary frames carry the same payloads, one or more lines per frame.
+Replays read the lines back from a capture file and ignore what is written to them.
+*/
+pub enum PostureConnection {
+    Stream {
+        lines: Lines<BufReader<ReadHalf<Box<dyn PostureStream>>>>,
+        writer: WriteHalf<Box<dyn PostureStream>>,
+    },
+    WebSocket {
+        socket: Box<WebSocketStream<Box<dyn PostureStream>>>,
+        pending: VecDeque<String>,
+    },
+    Replay(Box<CaptureReplay>),
+}
+
+pub async fn connect(address: &ServerAddress) -> io::Result<PostureConnection> {
+    let pinned_certificate = address.pinned_certificate.as_deref();
+
+    match address.transport {
+        Transport::Tcp => {
+            let stream = TcpStream::connect((address.host.as_str(), address.port)).await?;
+            let stream: Box<dyn PostureStream> = if address.tls {
+                tls::connect_tls(stream, &address.host, pinned_certificate).await?
+            } else {
+                Box::new(stream)
+            };
+            Ok(PostureConnection::from_stream(stream))
+        }
+        Transport::Unix => {
+            let stream = connect_unix(&address.socket_path).await?;
+            Ok(PostureConnection::from_stream(stream))
+        }
+        Transport::WebSocket => {
+            let uri: Uri = address
+                .url
+                .parse()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            let host = uri
+                .host()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "missing host"))?;
+            let secure = uri.scheme_str() == Some("wss");
+            let port = uri.port_u16().unwrap_or(if secure { 443 } else { 80 });
+
+            let stream = TcpStream::connect((host, port)).await?;
+            let stream: Box<dyn PostureStream> = if secure {
+                tls::connect_tls(stream, host, pinned_certificate).await?
+            } else {
+                Box::new(stream)
+            };
+
+            let (socket, _) = tokio_tungstenite::client_async(address.url.as_str(), stream)
+                .await
+                .map_err(io::Error::other)?;
+            Ok(PostureConnection::WebSocket {
+                socket: Box::new(socket),
+                pending: VecDeque::new(),
+            })
+        }
+        Transport::Replay => {
+            let replay = CaptureReplay::open(
+                Path::new(&address.capture_path),
+                address.capture_source.clone(),
+                address.replay_speed,
+            )
+            .await?;
+            Ok(PostureConnection::Replay(Box::new(replay)))
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn connect_unix(socket_path: &str) -> io::Result<Box<dyn PostureStream>> {
+    let stream = UnixStream::connect(socket_path).await?;
+    Ok(Box::new(stream))
+}
+
+#[cfg(not(unix))]
+async fn connect_unix(_socket_path: &str) -> io::Result<Box<dyn PostureStream>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Unix sockets are not supported on this platform",
+    ))
+}
+
+impl PostureConnection {
+    pub fn from_stream(stream: Box<dyn PostureStream>) -> Self {
+        let (read_half, writer) = tokio::io::split(stream);
+        PostureConnection::Stream {
+            lines: BufReader::new(read_half).lines(),
+            writer,
+        }
+    }
+
+    /*
+    Returns the next line without its line ending, or None once the server closed the connection.
+    Cancel safe: a partially received line is kept for the next call,
+    so this can be raced against timeouts in tokio::select!.
+    */
+    pub async fn read_line(&mut self) -> io::Result<Option<String>> {
+        match self {
+            PostureConnection::Stream { lines, .. } => lines
+                .next_line()
+                .await
+                .map(|line| line.map(|line| line.trim_end_matches('\r').to_string())),
+            PostureConnection::WebSocket { socket, pending } => loop {
+                if let Some(line) = pending.pop_front() {
+                    return Ok(Some(line));
+                }
+
+                let payload = match socket.next().await {
+                    Some(Ok(Message::Text(text))) => text.to_string(),
+                    Some(Ok(Message::Binary(data))) => String::from_utf8(data.to_vec())
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+                    Some(Ok(Message::Close(_))) | None => return Ok(None),
+                    // Pings are answered by tungstenite itself
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => return Err(io::Error::other(e)),
+                };
+
+                pending.extend(
+                    payload
+                        .lines()
+                        .map(|line| line.trim_end_matches('\r'))
+                        .filter(|line| !line.is_empty())
+                        .map(str::to_string),
+                );
+            },
+            PostureConnection::Replay(replay) => replay.read_line().await,
+        }
+    }
+
+    pub async fn write_line(&mut self, line: &str) -> io::Result<()> {
+        match self {
+            PostureConnection::Stream { writer, .. } => {
+                writer.write_all(format!("{}\n", line).as_bytes()).await?;
+                writer.flush().await
+            }
+            PostureConnection::WebSocket { socket, .. } => socket
+                .send(Message::text(line))
+                .await
+                .map_err(io::Error::other),
+            PostureConnection::Replay(_) => Ok(()),
+        }
+    }
+}
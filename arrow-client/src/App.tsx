@@ -8,10 +8,15 @@ import {
 	SessionLogsUpdate,
 	NotificationEvent,
 	PostureLog,
+	MonitoringState,
+	MonitoringStateUpdate,
+	CalibrationProgress,
+	CalibrationResult,
 	WeeklyStats as WeeklyStatsType
 } from "./types";
 import PostureDisplay from "./components/PostureDisplay";
 import ConnectionIndicator from "./components/ConnectionIndicator";
+import CalibrationPanel from "./components/CalibrationPanel";
 import SessionHistory from "./components/SessionHistory";
 import WeeklyStats from "./components/WeeklyStats";
 
@@ -24,6 +29,9 @@ function App() {
 	const [sessionLogs, setSessionLogs] = useState<PostureLog[]>([]);
 	const [weeklyStats, setWeeklyStats] = useState<WeeklyStatsType>({ days: [] });
 	const [isInitialized, setIsInitialized] = useState(false);
+	const [monitoringState, setMonitoringState] = useState<MonitoringState>("stopped");
+	const [calibrationProgress, setCalibrationProgress] = useState<CalibrationProgress | null>(null);
+	const [calibrationResult, setCalibrationResult] = useState<CalibrationResult | null>(null);
 
 	useEffect(() => {
 		let isCleanedUp = false;
@@ -73,6 +81,26 @@ function App() {
 					}
 				});
 
+				// Listen for monitoring being started, paused, resumed or stopped
+				const monitoringUnlisten = await listen<MonitoringStateUpdate>("monitoring-state", (event) => {
+					if (!isCleanedUp) {
+						setMonitoringState(event.payload.state);
+					}
+				});
+
+				// Listen for the calibration of the straight posture
+				const calibrationProgressUnlisten = await listen<CalibrationProgress>("calibration-progress", (event) => {
+					if (!isCleanedUp) {
+						setCalibrationProgress(event.payload);
+					}
+				});
+				const calibrationFinishedUnlisten = await listen<CalibrationResult>("calibration-finished", (event) => {
+					if (!isCleanedUp) {
+						setCalibrationProgress(null);
+						setCalibrationResult(event.payload);
+					}
+				});
+
 				// Listen for notification events (for frontend feedback)
 				const notificationUnlisten = await listen<NotificationEvent>("notification-triggered", (event) => {
 					if (!isCleanedUp) {
@@ -86,6 +114,9 @@ function App() {
 					connectionUnlisten();
 					logsUnlisten();
 					notificationUnlisten();
+					monitoringUnlisten();
+					calibrationProgressUnlisten();
+					calibrationFinishedUnlisten();
 				};
 			} catch (error) {
 				console.error("Failed to setup event listeners:", error);
@@ -97,6 +128,13 @@ function App() {
 		const init = async () => {
 			await initializeApp();
 			const cleanup = await setupEventListeners();
+			invoke<MonitoringState>("get_monitoring_state")
+				.then((state) => {
+					if (!isCleanedUp) {
+						setMonitoringState(state);
+					}
+				})
+				.catch(console.error);
 			return cleanup;
 		};
 
@@ -136,6 +174,38 @@ function App() {
 		}
 	};
 
+	// The new state comes back through the monitoring-state event
+	const togglePause = async () => {
+		const command = {
+			stopped: "start_monitoring",
+			running: "pause_monitoring",
+			paused: "resume_monitoring",
+		}[monitoringState];
+		try {
+			await invoke(command);
+		} catch (error) {
+			console.error("Failed to toggle monitoring:", error);
+		}
+	};
+
+	const startCalibration = async () => {
+		try {
+			setCalibrationResult(null);
+			await invoke("start_posture_calibration");
+		} catch (error) {
+			setCalibrationResult({ success: false, message: `${error}`, baseline: null });
+		}
+	};
+
+	const resetCalibration = async () => {
+		try {
+			await invoke("reset_posture_baseline");
+			setCalibrationResult({ success: true, message: "Calibration reset", baseline: null });
+		} catch (error) {
+			console.error("Failed to reset calibration:", error);
+		}
+	};
+
 	const fetchWeeklyStats = async () => {
 		try {
 			const stats = await invoke<WeeklyStatsType>("get_weekly_stats");
@@ -151,13 +221,21 @@ function App() {
 				<h1>Arrow - Posture Monitor</h1>
 				<ConnectionIndicator
 					status={connectionStatus}
+					paused={monitoringState !== "running"}
 					onRefresh={getConnectionDetails}
+					onTogglePause={togglePause}
 				/>
 			</header>
 
 			<div className="app-content">
 				<div className="posture-section">
 					<PostureDisplay postureUpdate={postureUpdate} />
+					<CalibrationPanel
+						progress={calibrationProgress}
+						result={calibrationResult}
+						onStart={startCalibration}
+						onReset={resetCalibration}
+					/>
 				</div>
 
 				<div className="history-section">
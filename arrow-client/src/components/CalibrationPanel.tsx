@@ -0,0 +1,40 @@
+import React from "react";
+import { CalibrationProgress, CalibrationResult } from "../types";
+
+interface CalibrationPanelProps {
+  progress: CalibrationProgress | null;
+  result: CalibrationResult | null;
+  onStart: () => void;
+  onReset: () => void;
+}
+
+const CalibrationPanel: React.FC<CalibrationPanelProps> = ({ progress, result, onStart, onReset }) => {
+  const calibrating = progress !== null && progress.progress < 1;
+
+  return (
+    <div className="calibration-panel">
+      {calibrating ? (
+        <>
+          <p className="calibration-instructions">Sit straight and still...</p>
+          <progress value={progress.progress} max={1} />
+        </>
+      ) : (
+        <>
+          {result && (
+            <p className={`calibration-result ${result.success ? "good" : "bad"}`}>
+              {result.message}
+            </p>
+          )}
+          <button className="refresh-button" onClick={onStart} title="Measure your straight posture">
+            🎯 Calibrate
+          </button>
+          <button className="refresh-button" onClick={onReset} title="Forget the calibration">
+            ↩️ Reset
+          </button>
+        </>
+      )}
+    </div>
+  );
+};
+
+export default CalibrationPanel;
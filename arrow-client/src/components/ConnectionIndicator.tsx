@@ -3,10 +3,12 @@ import { ConnectionStatus } from "../types";
 
 interface ConnectionIndicatorProps {
   status: ConnectionStatus;
+  paused: boolean;
   onRefresh: () => void;
+  onTogglePause: () => void;
 }
 
-const ConnectionIndicator: React.FC<ConnectionIndicatorProps> = ({ status, onRefresh }) => {
+const ConnectionIndicator: React.FC<ConnectionIndicatorProps> = ({ status, paused, onRefresh, onTogglePause }) => {
   const getStatusClass = (): string => {
     return status.connected ? "connected" : "disconnected";
   };
@@ -26,8 +28,29 @@ const ConnectionIndicator: React.FC<ConnectionIndicatorProps> = ({ status, onRef
       
       <div className="connection-message">
         {status.message}
+        {status.server_address && (
+          <span className="connection-address"> ({status.server_address})</span>
+        )}
       </div>
+
+      {status.sources && status.sources.length > 1 && (
+        <ul className="connection-sources">
+          {status.sources.map((source) => (
+            <li key={source.name} title={source.message}>
+              {source.connected ? (source.stale ? "🟡" : "🟢") : "🔴"} {source.name}
+            </li>
+          ))}
+        </ul>
+      )}
       
+      <button
+        className="refresh-button"
+        onClick={onTogglePause}
+        title={paused ? "Resume monitoring" : "Pause monitoring and release the camera"}
+      >
+        {paused ? "▶️" : "⏸️"}
+      </button>
+
       <button 
         className="refresh-button"
         onClick={onRefresh}
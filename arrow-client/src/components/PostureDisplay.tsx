@@ -22,7 +22,9 @@ const PostureDisplay: React.FC<PostureDisplayProps> = ({ postureUpdate }) => {
   };
 
   const getPostureStatus = (): "good" | "bad" | "unknown" => {
-    if (!postureUpdate) return "unknown";
+    if (!postureUpdate || postureUpdate.posture === "Stale" || postureUpdate.posture === "Paused") {
+      return "unknown";
+    }
     return postureUpdate.posture === "Straight" ? "good" : "bad";
   };
 
@@ -46,6 +48,16 @@ const PostureDisplay: React.FC<PostureDisplayProps> = ({ postureUpdate }) => {
             <span className="posture-type">
               Status: {postureUpdate.posture.replace(/([A-Z])/g, ' $1').trim()}
             </span>
+
+            {postureUpdate.issues.length > 1 && (
+              <ul className="posture-issues">
+                {postureUpdate.issues.map((issue) => (
+                  <li key={issue.posture}>
+                    {issue.posture.replace(/([A-Z])/g, ' $1').trim()} ({issue.severity.toFixed(1)}×)
+                  </li>
+                ))}
+              </ul>
+            )}
             
             {postureUpdate.metrics && (
               <div className="posture-metrics">
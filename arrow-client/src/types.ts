@@ -9,6 +9,8 @@ export type PostureType =
   | "BodyTiltLeft"
   | "BodyTiltRight"
   | "Straight"
+  | "Stale"
+  | "Paused"
   | "Unknown";
 
 export interface Posture {
@@ -30,15 +32,131 @@ export interface PostureMetrics {
   right_shoulder: Point3D;
 }
 
+// MediaPipe landmark name (e.g. "nose", "left_hip") to coordinates
+export type PoseLandmarks = Record<string, Point3D>;
+
 export interface PostureUpdate {
   posture: PostureType;
   message: string;
+  // Posture of this frame alone, before the smoothing
+  raw_posture: PostureType | null;
+  // Every issue of this frame, the most important first
+  issues: PostureIssue[];
   metrics?: PostureMetrics;
+  landmarks: PoseLandmarks;
+}
+
+export interface PostureIssue {
+  posture: PostureType;
+  // How far past its threshold, 1 at the threshold
+  severity: number;
 }
 
 export interface ConnectionStatus {
   connected: boolean;
   message: string;
+  server_address?: string;
+  // Number of consecutive failed connection attempts
+  attempt?: number;
+  // Unix timestamp in milliseconds of the next connection attempt
+  next_retry_at?: number | null;
+  last_error_kind?: string | null;
+  // Health of every source, the fields above describe the one this status is about
+  sources?: SourceStatus[];
+}
+
+export interface SourceStatus {
+  name: string;
+  server_address: string;
+  connected: boolean;
+  // Connected but no recent frames
+  stale: boolean;
+  message: string;
+  attempt: number;
+  last_frame_at: number | null;
+  server_info: ServerInfo | null;
+}
+
+// Additional posture server fused with the primary one, e.g. a side camera
+export type CameraView = "front" | "left" | "right";
+
+export interface SourceConfig {
+  name: string;
+  address: ServerAddress;
+  weight: number;
+  view: CameraView;
+}
+
+export type DemoScenario =
+  | { kind: "cycle"; hold_secs: number }
+  | { kind: "slouch_after"; straight_minutes: number }
+  | { kind: "frequent_head_tilts"; interval_secs: number }
+  | { kind: "steady"; posture: PostureType };
+
+export interface DemoSettings {
+  enabled: boolean;
+  scenario: DemoScenario;
+  noise: number;
+  dropouts_per_minute: number;
+  frame_rate: number;
+  speed: number;
+}
+
+export interface ReconnectPolicy {
+  initial_delay_ms: number;
+  max_delay_ms: number;
+  multiplier: number;
+  jitter: number;
+  max_attempts: number | null;
+}
+
+export interface StreamStats {
+  source: string;
+  frames: number;
+  frames_per_second: number;
+  dropped_frames: number;
+  gaps: number;
+  out_of_order_frames: number;
+  latency_ms: number | null;
+  max_latency_ms: number | null;
+}
+
+export interface QueueStats {
+  name: string;
+  depth: number;
+  capacity: number;
+  dropped: number;
+}
+
+export interface ServerInfo {
+  server_name: string;
+  server_version: string;
+  protocol_version: number;
+  frame_rate: number;
+  landmarks: string[];
+  commands: string[];
+}
+
+export type Transport = "tcp" | "unix" | "web_socket" | "replay";
+
+export interface ServerAddress {
+  transport: Transport;
+  host: string;
+  port: number;
+  // Only used by the unix transport
+  socket_path: string;
+  // Only used by the web_socket transport, e.g. "ws://127.0.0.1:9877" or "wss://..."
+  url: string;
+  // TLS for the tcp transport, web_socket uses TLS for wss:// URLs
+  tls: boolean;
+  // SHA-256 fingerprint of the server certificate to trust instead of public CAs
+  pinned_certificate?: string | null;
+  auth_token?: string | null;
+  // Only used by the replay transport
+  capture_path?: string;
+  // 1 replays at the recorded pace
+  replay_speed?: number;
+  capture_source?: string | null;
 }
 
 export interface PostureLog {
@@ -49,6 +167,130 @@ export interface PostureLog {
   };
 }
 
+export interface StreamStale {
+  silence_ms: number;
+  previous_posture: string;
+}
+
+export type MonitoringState = "stopped" | "running" | "paused";
+
+export interface MonitoringStateUpdate {
+  state: MonitoringState;
+}
+
+// A posture server announced on the local network
+export interface DiscoveredServer {
+  server_name: string;
+  server_version: string;
+  protocol_version: number;
+  address: ServerAddress;
+  incompatibility: string | null;
+  last_seen_ms: number;
+}
+
+export interface DiscoveredServersUpdate {
+  servers: DiscoveredServer[];
+}
+
+export interface DiscoverySettings {
+  enabled: boolean;
+  port: number;
+  multicast_group: string | null;
+  auto_connect: string | null;
+  expiry_ms: number;
+}
+
+export type ServerProcessState =
+  | "disabled"
+  | "starting"
+  | "running"
+  | "restarting"
+  | "failed"
+  | "stopped";
+
+// Health of the posture server started by the app
+export interface ServerProcessStatus {
+  state: ServerProcessState;
+  pid: number | null;
+  restarts: number;
+  last_exit: string | null;
+  last_error_output: string | null;
+  message: string;
+}
+
+export interface ServerProcessSettings {
+  enabled: boolean;
+  interpreter: string;
+  script_path: string;
+  args: string[];
+  env: Record<string, string>;
+  working_dir: string | null;
+  restart: ReconnectPolicy;
+}
+
+// Thresholds used to classify the posture, saved in the settings
+export interface PostureThresholds {
+  min_visibility: number;
+  slouch_depth: number;
+  lean_in_depth: number;
+  slouch_shoulder_depth: number;
+  side_slouch_gap: number;
+  side_lean_in_gap: number;
+  head_tilt_slope: number;
+  body_tilt_slope: number;
+}
+
+// Which classifier turns the measures into postures
+export type ClassifierSettings =
+  | { kind: "rules" }
+  | { kind: "linear"; model_path: string };
+
+// How long a new posture must last before it is logged and notified
+export interface SmoothingSettings {
+  min_dwell_ms: number;
+  min_frames: number;
+  hysteresis: number;
+}
+
+// Neutral posture of the user, measured by the calibration
+export interface PostureBaseline {
+  depth_gap: number;
+  shoulder_depth: number;
+  ear_slope: number;
+  shoulder_slope: number;
+  side_gap: number;
+  samples: number;
+  calibrated_at: number;
+}
+
+export interface CalibrationProgress {
+  progress: number;
+  samples: number;
+  elapsed_ms: number;
+  duration_ms: number;
+}
+
+export interface CalibrationResult {
+  success: boolean;
+  message: string;
+  baseline: PostureBaseline | null;
+}
+
+export interface StreamSettings {
+  frame_timeout_ms: number;
+  reconnect_timeout_ms: number;
+}
+
+// Line counters for the current connection
+export interface StreamDiagnostics {
+  source: string;
+  server_address: string;
+  good_lines: number;
+  rejected_lines: number;
+  unknown_format_lines: number;
+  last_error: string | null;
+}
+
 export interface SessionLogsUpdate {
   logs: PostureLog[];
 }
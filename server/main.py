@@ -1,12 +1,27 @@
 import cv2
+import json
 import mediapipe as mp
+import select
 import socket
+import threading
+import time
 
 HOST = '127.0.0.1'
 PORT = 9876
 
+SERVER_NAME = 'arrow-mediapipe-server'
+SERVER_VERSION = '0.1.0'
+PROTOCOL_VERSION = 1
+# All 33 MediaPipe pose landmarks, e.g. 'nose', 'left_ear', 'left_hip'
+SUPPORTED_LANDMARKS = [landmark.name.lower() for landmark in mp.solutions.pose.PoseLandmark]
+SUPPORTED_COMMANDS = ['pause', 'resume', 'set_frame_rate', 'shutdown']
+# The server only listens on loopback, so it is only announced to the clients on this machine
+BEACON_ADDRESS = ('127.0.0.1', 9875)
+BEACON_INTERVAL = 2
+
 POSTURES = ["STRAIGHT", "SLOUCHING_BACK", "LEANING_IN", "HEAD_TILT_RIGHT", "HEAD_TILT_LEFT", "BODY_TILT_RIGHT", "BODY_TILT_LEFT"]
 
+# Same thresholds as the defaults of the client, see PostureThresholds in its settings
 def get_posture(left_ear, right_ear, left_shoulder, right_shoulder):
     avg_ear_depth = (left_ear.z + right_ear.z) / 2
     avg_shoulder_depth = (left_shoulder.z + right_shoulder.z) / 2
@@ -19,38 +34,146 @@ def get_posture(left_ear, right_ear, left_shoulder, right_shoulder):
 
     # Check head tilt
     ear_slope = (left_ear.y - right_ear.y) / (left_ear.x - right_ear.x)
-    if ear_slope > 0.05:
+    if ear_slope > 0.10:
         return POSTURES[3]
-    if ear_slope < -0.05:
+    if ear_slope < -0.10:
         return POSTURES[4]
 
     # Check body tilt
     shoulder_slope = (left_shoulder.y - right_shoulder.y) / (left_shoulder.x - right_shoulder.x)
-    if shoulder_slope > 0.05:
+    if shoulder_slope > 0.10:
         return POSTURES[5]
-    if shoulder_slope < -0.05:
+    if shoulder_slope < -0.10:
         return POSTURES[6]
 
     # Default to STRAIGHT
     return POSTURES[0]
 
+def server_hello(frame_rate):
+    hello = {
+        'type': 'hello',
+        'server_name': SERVER_NAME,
+        'server_version': SERVER_VERSION,
+        'protocol_version': PROTOCOL_VERSION,
+        'frame_rate': frame_rate,
+        'landmarks': SUPPORTED_LANDMARKS,
+        'commands': SUPPORTED_COMMANDS,
+    }
+    return json.dumps(hello) + "\r\n"
+
+def announce():
+    # Sends the discovery beacon forever, run in a daemon thread
+    beacon = json.dumps({
+        'type': 'beacon',
+        'server_name': SERVER_NAME,
+        'server_version': SERVER_VERSION,
+        'protocol_version': PROTOCOL_VERSION,
+        'port': PORT,
+        'tls': False,
+    }).encode("utf-8")
+    with socket.socket(socket.AF_INET, socket.SOCK_DGRAM) as s:
+        while True:
+            try:
+                s.sendto(beacon, BEACON_ADDRESS)
+            except OSError:
+                pass
+            time.sleep(BEACON_INTERVAL)
+
+def command_ack(command_id, ok, message):
+    return json.dumps({'type': 'ack', 'id': command_id, 'ok': ok, 'message': message}) + "\r\n"
+
+def read_lines(conn, buffer, timeout):
+    # Wait up to timeout seconds (forever if None) for data from the client
+    if not select.select([conn], [], [], timeout)[0]:
+        return []
+    data = conn.recv(4096)
+    if not data:
+        raise ConnectionResetError
+    buffer.extend(data)
+    *lines, rest = buffer.split(b"\n")
+    buffer[:] = rest
+    return [line.decode('utf-8', 'replace').strip() for line in lines]
+
+class Stream:
+    def __init__(self):
+        self.cap = cv2.VideoCapture(0)
+        # No limit until the client sets a frame rate
+        self.frame_interval = 0
+        self.shutdown = False
+
+    def handle_command(self, line):
+        try:
+            message = json.loads(line)
+        except ValueError:
+            return None
+        if message.get('type') != 'command':
+            return None
+
+        command_id = message.get('id')
+        command = message.get('command')
+        if command == 'pause':
+            # Release the webcam so its light goes off while paused
+            if self.cap is not None:
+                self.cap.release()
+                self.cap = None
+            return command_ack(command_id, True, "Camera released")
+        if command == 'resume':
+            if self.cap is None:
+                self.cap = cv2.VideoCapture(0)
+            if not self.cap.isOpened():
+                # Stay paused, the client can try again
+                self.cap.release()
+                self.cap = None
+                return command_ack(command_id, False, "Failed to open the camera")
+            return command_ack(command_id, True, "Camera resumed")
+        if command == 'set_frame_rate':
+            frame_rate = message.get('frame_rate')
+            if not isinstance(frame_rate, (int, float)) or frame_rate <= 0:
+                return command_ack(command_id, False, "Invalid frame rate")
+            self.frame_interval = 1 / frame_rate
+            return command_ack(command_id, True, f"Frame rate set to {frame_rate}")
+        if command == 'shutdown':
+            self.shutdown = True
+            return command_ack(command_id, True, "Shutting down")
+        return command_ack(command_id, False, f"Unsupported command {command}")
+
 def main():
     # Setup MediaPipe Pose
     mp_pose = mp.solutions.pose
     pose = mp_pose.Pose()
 
     # Open Webcam
-    cap = cv2.VideoCapture(0)
+    stream = Stream()
 
     with socket.socket(socket.AF_INET, socket.SOCK_STREAM) as s:
         s.bind((HOST, PORT))
         s.listen()
         print("Server listening...")
+        threading.Thread(target=announce, daemon=True).start()
         conn, addr = s.accept()
         with conn:
             print(f"Connected by {addr}")
-            while cap.isOpened():
-                ret, frame = cap.read()
+            conn.sendall(server_hello(stream.cap.get(cv2.CAP_PROP_FPS)).encode("utf-8"))
+            buffer = bytearray()
+            next_frame = time.monotonic()
+            seq = 0
+            while not stream.shutdown:
+                # Answer the client commands, and block on them while paused
+                paused = stream.cap is None
+                timeout = None if paused else max(0, next_frame - time.monotonic())
+                for line in read_lines(conn, buffer, timeout):
+                    ack = stream.handle_command(line)
+                    if ack:
+                        conn.sendall(ack.encode("utf-8"))
+                if stream.cap is None or time.monotonic() < next_frame:
+                    continue
+                if not stream.cap.isOpened():
+                    break
+                next_frame = time.monotonic() + stream.frame_interval
+
+                ret, frame = stream.cap.read()
+                captured_at = time.time() * 1000
+                seq += 1
                 if not ret:
                     print("Failed to grab frame")
                     break
@@ -72,18 +195,21 @@ def main():
                 if results.pose_landmarks:
                     landmarks = results.pose_landmarks.landmark
 
-                    # Get coordinates
-                    left_ear = landmarks[mp_pose.PoseLandmark.LEFT_EAR]
-                    right_ear = landmarks[mp_pose.PoseLandmark.RIGHT_EAR]
-                    left_shoulder = landmarks[mp_pose.PoseLandmark.LEFT_SHOULDER]
-                    right_shoulder = landmarks[mp_pose.PoseLandmark.RIGHT_SHOULDER]
-        
-                    metrics = (
-                        f"{left_ear.x:.4f}|{left_ear.y:.4f}|{left_ear.z:.4f}|{left_ear.visibility:.4f}|"
-                        f"{right_ear.x:.4f}|{right_ear.y:.4f}|{right_ear.z:.4f}|{right_ear.visibility:.4f}|"
-                        f"{left_shoulder.x:.4f}|{left_shoulder.y:.4f}|{left_shoulder.z:.4f}|{left_shoulder.visibility:.4f}|"
-                        f"{right_shoulder.x:.4f}|{right_shoulder.y:.4f}|{right_shoulder.z:.4f}|{right_shoulder.visibility:.4f}\r\n"
-                    )
+                    frame_metrics = {
+                        'version': PROTOCOL_VERSION,
+                        'seq': seq,
+                        'captured_at': round(captured_at, 1),
+                        'landmarks': {
+                            landmark.name.lower(): {
+                                'x': round(landmarks[landmark].x, 4),
+                                'y': round(landmarks[landmark].y, 4),
+                                'z': round(landmarks[landmark].z, 4),
+                                'visibility': round(landmarks[landmark].visibility, 4),
+                            }
+                            for landmark in mp_pose.PoseLandmark
+                        },
+                    }
+                    metrics = json.dumps(frame_metrics) + "\r\n"
 
                     print(metrics)
 
@@ -92,7 +218,8 @@ def main():
                 if cv2.waitKey(1) & 0xFF == ord('q'):
                     break
 
-    cap.release()
+    if stream.cap is not None:
+        stream.cap.release()
 
 if __name__ == "__main__":
     main()
@@ -3,17 +3,144 @@
 Test server for Arrow posture monitoring - sends fake posture data for testing
 """
 
+import argparse
+import json
+import os
 import socket
+import ssl
 import time
 import random
+import select
+import threading
 
 HOST = '127.0.0.1'
 PORT = 9876
 
-def generate_fake_metrics():
-    """Generate fake posture metrics for testing"""
+PROTOCOL_VERSION = 1
+SUPPORTED_LANDMARKS = ['left_ear', 'right_ear', 'left_shoulder', 'right_shoulder']
+SUPPORTED_COMMANDS = ['pause', 'resume', 'set_frame_rate', 'start_calibration', 'shutdown']
+# How long start_calibration sends good posture only
+CALIBRATION_SECONDS = 5
+# Where the clients listen for beacons, see DiscoverySettings in the client
+BEACON_PORT = 9875
+BEACON_GROUP = '239.255.98.76'
+BEACON_INTERVAL = 2
+
+def server_hello(frame_rate):
+    """Handshake line describing this server, sent once per connection"""
+    hello = {
+        'type': 'hello',
+        'server_name': 'arrow-test-server',
+        'server_version': '0.1.0',
+        'protocol_version': PROTOCOL_VERSION,
+        'frame_rate': frame_rate,
+        'landmarks': SUPPORTED_LANDMARKS,
+        'commands': SUPPORTED_COMMANDS,
+    }
+    return json.dumps(hello) + "\r\n"
+
+def command_ack(command_id, ok, message):
+    """Answer to a client command, matched by its id"""
+    return json.dumps({'type': 'ack', 'id': command_id, 'ok': ok, 'message': message}) + "\r\n"
+
+def server_error(code, message):
+    """Sent instead of the hello when the client is refused"""
+    return json.dumps({'type': 'error', 'code': code, 'message': message}) + "\r\n"
+
+def server_beacon(name, port, tls):
+    """Announcement of this server to the clients on the local network"""
+    beacon = {
+        'type': 'beacon',
+        'server_name': name,
+        'server_version': '0.1.0',
+        'protocol_version': PROTOCOL_VERSION,
+        'port': port,
+        'tls': tls,
+    }
+    return json.dumps(beacon).encode("utf-8")
+
+def announce(beacon, target):
+    """Sends the beacon to the target address forever, run in a daemon thread"""
+    with socket.socket(socket.AF_INET, socket.SOCK_DGRAM) as s:
+        s.setsockopt(socket.SOL_SOCKET, socket.SO_BROADCAST, 1)
+        s.setsockopt(socket.IPPROTO_IP, socket.IP_MULTICAST_TTL, 1)
+        while True:
+            try:
+                s.sendto(beacon, (target, BEACON_PORT))
+            except OSError as e:
+                print(f"Failed to send beacon: {e}")
+            time.sleep(BEACON_INTERVAL)
+
+def read_client_hello(conn):
+    """Read the hello line the client sends right after connecting"""
+    conn.settimeout(5)
+    try:
+        line = conn.makefile('r', encoding='utf-8').readline()
+    finally:
+        conn.settimeout(None)
+    try:
+        return json.loads(line)
+    except ValueError:
+        return {}
+
+def read_lines(conn, buffer, timeout):
+    """Wait up to timeout seconds for data from the client, returns the complete lines received"""
+    # Decrypted TLS data can be waiting without the socket being readable
+    pending = isinstance(conn, ssl.SSLSocket) and conn.pending()
+    if not pending and not select.select([conn], [], [], timeout)[0]:
+        return []
+    data = conn.recv(4096)
+    if not data:
+        raise ConnectionResetError
+    buffer.extend(data)
+    *lines, rest = buffer.split(b"\n")
+    buffer[:] = rest
+    return [line.decode('utf-8', 'replace').strip() for line in lines]
+
+class StreamState:
+    """What the client asked this connection to do"""
+    def __init__(self, frame_rate):
+        self.frame_rate = frame_rate
+        self.paused = False
+        self.calibrating_until = 0
+        self.shutdown = False
+
+def handle_command(line, state):
+    """Apply a client command, returns the ack line to send or None for other messages"""
+    try:
+        message = json.loads(line)
+    except ValueError:
+        return None
+    if message.get('type') != 'command':
+        return None
+
+    command_id = message.get('id')
+    command = message.get('command')
+    print(f"Received command {command}")
+    if command == 'pause':
+        state.paused = True
+        return command_ack(command_id, True, "Paused")
+    if command == 'resume':
+        state.paused = False
+        return command_ack(command_id, True, "Resumed")
+    if command == 'set_frame_rate':
+        frame_rate = message.get('frame_rate')
+        if not isinstance(frame_rate, (int, float)) or frame_rate <= 0:
+            return command_ack(command_id, False, "Invalid frame rate")
+        state.frame_rate = frame_rate
+        return command_ack(command_id, True, f"Frame rate set to {frame_rate}")
+    if command == 'start_calibration':
+        state.calibrating_until = time.monotonic() + CALIBRATION_SECONDS
+        return command_ack(command_id, True, f"Calibrating for {CALIBRATION_SECONDS}s, sit straight")
+    if command == 'shutdown':
+        state.shutdown = True
+        return command_ack(command_id, True, "Shutting down")
+    return command_ack(command_id, False, f"Unknown command {command}")
+
+def generate_fake_metrics(wire_format='pipe', posture_type=None, seq=None):
+    """Generate fake posture metrics for testing, seq numbers JSON frames"""
     # Generate fake coordinates for good/bad posture
-    posture_type = random.choice(['good', 'bad'])
+    posture_type = posture_type or random.choice(['good', 'bad'])
     
     if posture_type == 'good':
         # Straight posture - ears above shoulders
@@ -28,6 +155,22 @@ def generate_fake_metrics():
         left_shoulder = {'x': 0.3, 'y': 0.4, 'z': 0.2, 'visibility': 0.95}
         right_shoulder = {'x': 0.7, 'y': 0.4, 'z': 0.2, 'visibility': 0.95}
     
+    if wire_format == 'json':
+        frame = {
+            'version': PROTOCOL_VERSION,
+            'landmarks': {
+                'left_ear': left_ear,
+                'right_ear': right_ear,
+                'left_shoulder': left_shoulder,
+                'right_shoulder': right_shoulder,
+            },
+            'source': 'test_server',
+        }
+        if seq is not None:
+            frame['seq'] = seq
+            frame['captured_at'] = time.time() * 1000
+        return json.dumps(frame) + "\r\n", posture_type
+
     # Format as expected by the client
     metrics = (
         f"{left_ear['x']:.4f}|{left_ear['y']:.4f}|{left_ear['z']:.4f}|{left_ear['visibility']:.4f}|"
@@ -39,12 +182,56 @@ def generate_fake_metrics():
     return metrics, posture_type
 
 def main():
+    parser = argparse.ArgumentParser(description="Arrow posture test server")
+    parser.add_argument('--host', default=HOST)
+    parser.add_argument('--port', type=int, default=PORT)
+    parser.add_argument('--format', choices=['pipe', 'json'], default='pipe',
+                        help="wire format of the metric lines")
+    parser.add_argument('--unix', metavar='PATH',
+                        help="listen on a Unix domain socket instead of TCP")
+    parser.add_argument('--no-handshake', action='store_true',
+                        help="behave like a legacy server and skip the hello")
+    parser.add_argument('--tls-cert', metavar='PEM',
+                        help="serve over TLS with this certificate (requires --tls-key)")
+    parser.add_argument('--tls-key', metavar='PEM',
+                        help="private key for --tls-cert")
+    parser.add_argument('--token',
+                        help="refuse clients whose hello doesn't carry this token")
+    parser.add_argument('--name', default='arrow-test-server',
+                        help="server name announced in the discovery beacon")
+    parser.add_argument('--beacon-address', default=BEACON_GROUP,
+                        help="where to send the beacon: the multicast group (default), "
+                             "255.255.255.255 to broadcast or 127.0.0.1 for loopback")
+    parser.add_argument('--no-beacon', action='store_true',
+                        help="don't announce the server")
+    args = parser.parse_args()
+
+    tls_context = None
+    if args.tls_cert:
+        tls_context = ssl.SSLContext(ssl.PROTOCOL_TLS_SERVER)
+        tls_context.load_cert_chain(args.tls_cert, args.tls_key)
+
     print("Starting test server for Arrow posture monitoring...")
-    print(f"Listening on {HOST}:{PORT}")
-    
-    with socket.socket(socket.AF_INET, socket.SOCK_STREAM) as s:
+
+    if args.unix:
+        print(f"Listening on unix:{args.unix} ({args.format} format)")
+        if os.path.exists(args.unix):
+            os.remove(args.unix)
+        s = socket.socket(socket.AF_UNIX, socket.SOCK_STREAM)
+        s.bind(args.unix)
+    else:
+        print(f"Listening on {args.host}:{args.port} ({args.format} format"
+              f"{', TLS' if tls_context else ''})")
+        s = socket.socket(socket.AF_INET, socket.SOCK_STREAM)
         s.setsockopt(socket.SOL_SOCKET, socket.SO_REUSEADDR, 1)
-        s.bind((HOST, PORT))
+        s.bind((args.host, args.port))
+
+        if not args.no_beacon:
+            print(f"Announcing '{args.name}' to {args.beacon_address}:{BEACON_PORT}")
+            beacon = server_beacon(args.name, args.port, tls_context is not None)
+            threading.Thread(target=announce, args=(beacon, args.beacon_address), daemon=True).start()
+
+    with s:
         s.listen()
         print("Server listening...")
         
@@ -52,17 +239,54 @@ def main():
             while True:
                 conn, addr = s.accept()
                 print(f"Connected by {addr}")
+
+                if tls_context:
+                    try:
+                        conn = tls_context.wrap_socket(conn, server_side=True)
+                    except (ssl.SSLError, OSError) as e:
+                        print(f"TLS handshake failed: {e}")
+                        conn.close()
+                        continue
                 
                 with conn:
                     try:
-                        # Send test data every 2 seconds
-                        while True:
-                            metrics, posture_type = generate_fake_metrics()
-                            print(f"Sending {posture_type} posture data")
-                            conn.sendall(metrics.encode("utf-8"))
-                            time.sleep(2)
+                        if args.token:
+                            hello = read_client_hello(conn)
+                            if hello.get('auth_token') != args.token:
+                                print("Refusing client with a missing or wrong token")
+                                conn.sendall(server_error('unauthorized', 'Invalid token').encode("utf-8"))
+                                continue
+
+                        # Send test data every 2 seconds by default
+                        state = StreamState(0.5)
+                        if not args.no_handshake:
+                            conn.sendall(server_hello(state.frame_rate).encode("utf-8"))
+
+                        buffer = bytearray()
+                        next_frame = time.monotonic()
+                        seq = 0
+                        while not state.shutdown:
+                            if not state.paused and time.monotonic() >= next_frame:
+                                calibrating = time.monotonic() < state.calibrating_until
+                                seq += 1
+                                metrics, posture_type = generate_fake_metrics(
+                                    args.format, 'good' if calibrating else None, seq)
+                                print(f"Sending {posture_type} posture data")
+                                conn.sendall(metrics.encode("utf-8"))
+                                next_frame = time.monotonic() + 1 / state.frame_rate
+
+                            # Commands are answered while waiting for the next frame
+                            timeout = None if state.paused else max(0, next_frame - time.monotonic())
+                            for line in read_lines(conn, buffer, timeout):
+                                ack = handle_command(line, state)
+                                if ack:
+                                    conn.sendall(ack.encode("utf-8"))
+
+                        if state.shutdown:
+                            print("Shutdown requested by the client")
+                            break
                             
-                    except (ConnectionResetError, BrokenPipeError):
+                    except (ConnectionResetError, BrokenPipeError, socket.timeout):
                         print("Client disconnected")
                     except KeyboardInterrupt:
                         print("Stopping server...")